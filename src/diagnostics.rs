@@ -0,0 +1,660 @@
+use std::io::IsTerminal;
+
+use crate::{
+    frontend::{
+        lexer::LexError,
+        parser::{ParseError, MAX_NESTING_DEPTH},
+        span::Span,
+        span::Spanned,
+    },
+    runtime::environment::{EnvError, MAX_CALL_DEPTH},
+};
+
+/// A stable error code with a longer explanation, as printed by `explain <code>`.
+pub struct ErrorCode {
+    pub code: &'static str,
+    pub summary: &'static str,
+    pub explanation: &'static str,
+}
+
+pub const ERROR_CODES: &[ErrorCode] = &[
+    ErrorCode {
+        code: "E0001",
+        summary: "use of an undeclared variable",
+        explanation: "\
+A name was used that is not declared in the current scope or any scope around it.
+
+    let a = b + 1; // `b` was never declared
+
+Declare the variable before using it:
+
+    let b = 2;
+    let a = b + 1;",
+    },
+    ErrorCode {
+        code: "E0002",
+        summary: "variable declared twice",
+        explanation: "\
+A `let` or `const` declaration used a name that is already declared in the same scope.
+
+    let a = 1;
+    let a = 2; // `a` already exists
+
+To change the value of an existing variable, assign to it instead:
+
+    let a = 1;
+    a = 2;",
+    },
+    ErrorCode {
+        code: "E0003",
+        summary: "invalid operand types",
+        explanation: "\
+A binary operator was applied to values whose types it does not support.
+
+    let a = \"count: \" + 3; // a string and a number
+
+Strings can only be concatenated with other strings, and arithmetic operators only work on \
+numbers. Ordering comparisons such as `<` work between numbers and between strings, and the \
+bounds of a range `..` must be ints.",
+    },
+    ErrorCode {
+        code: "E0004",
+        summary: "invalid operand type",
+        explanation: "\
+An operator was applied to a value whose type it does not support.
+
+    let a = 1 && true; // `&&` needs bools on both sides
+    let b = -\"text\";  // strings cannot be negated
+
+The logical operators `!`, `&&` and `||` only work on bools, and the unary `-` and `+` only \
+work on numbers. Compare values explicitly to get a bool:
+
+    let a = 1 != 0 && true;",
+    },
+    ErrorCode {
+        code: "E0005",
+        summary: "non-bool condition in strict mode",
+        explanation: "\
+In strict mode, the condition of an `if` or of a `while` loop must evaluate to a bool.
+
+    let count = 3;
+    if count { ... } // `count` is an int
+    while count { count = count - 1; }
+
+Outside of strict mode, conditions follow truthiness instead: `null`, `false`, `0`, `0.0`, NaN \
+and the empty string count as false, every other value as true. In strict mode, compare \
+explicitly:
+
+    if count != 0 { ... }
+    while count > 0 { count = count - 1; }",
+    },
+    ErrorCode {
+        code: "E0006",
+        summary: "value is not iterable",
+        explanation: "\
+A `for` loop was given a value that has no elements to iterate over.
+
+    for i in 10 { ... } // an int is not a collection
+
+Ranges, strings, arrays and maps can be iterated, maps giving their keys. To count up to a \
+number, loop over a range:
+
+    for i in 0..10 { ... }  // 0 to 9
+    for i in 0..=10 { ... } // 0 to 10",
+    },
+    ErrorCode {
+        code: "E0007",
+        summary: "wrong number of arguments",
+        explanation: "\
+A function was called with more or fewer arguments than it has parameters.
+
+    fn add(a, b) { return a + b; }
+    add(1); // `add` takes 2 arguments
+
+Pass exactly one argument for each parameter:
+
+    add(1, 2);",
+    },
+    ErrorCode {
+        code: "E0008",
+        summary: "value is not callable",
+        explanation: "\
+A call `f(...)` was made on a value that isn't a function.
+
+    let count = 3;
+    count(); // `count` is an int
+
+Only functions, declared with `fn`, can be called.",
+    },
+    ErrorCode {
+        code: "E0009",
+        summary: "negative array index",
+        explanation: "\
+An array was indexed with a number below zero.
+
+    let a = [1, 2, 3];
+    a[-1]; // indices start at 0
+
+Indices count from 0 for the first element. To get the last element, use an index one less \
+than the length of the array:
+
+    a[2];",
+    },
+    ErrorCode {
+        code: "E0010",
+        summary: "array index out of bounds",
+        explanation: "\
+An array was indexed past its last element, either to read or to assign.
+
+    let a = [1, 2, 3];
+    a[3] = 4; // `a` has no element at index 3
+
+Arrays don't grow when assigned to: only existing elements, from index 0 to the length minus \
+one, can be read or changed.",
+    },
+    ErrorCode {
+        code: "E0011",
+        summary: "missing map key",
+        explanation: "\
+A map was read at a key it doesn't contain.
+
+    let user = { name: \"x\" };
+    user.age; // `user` has no `age` key
+
+Assigning to a missing key adds it to the map, so give it a value before reading it:
+
+    user.age = 3;
+    user.age;",
+    },
+    ErrorCode {
+        code: "E0012",
+        summary: "assignment to a constant",
+        explanation: "\
+A value was assigned to a name declared with `const`.
+
+    const limit = 10;
+    limit = 20; // constants can't change
+
+If the value needs to change, declare it with `let` instead:
+
+    let limit = 10;
+    limit = 20;
+
+The elements of an array or a map held by a constant can still be assigned to.",
+    },
+    ErrorCode {
+        code: "E0013",
+        summary: "division by zero",
+        explanation: "\
+An int was divided by zero with `/`, or its remainder by zero was taken with `%`.
+
+    let ratio = total / count; // fails when `count` is 0
+
+Check the divisor first. Dividing floats by zero is allowed and gives an infinity or NaN:
+
+    if count != 0 { ratio = total / count; }",
+    },
+    ErrorCode {
+        code: "E0014",
+        summary: "integer overflow",
+        explanation: "\
+Int arithmetic gave a result outside of the range of ints, -2147483648 to 2147483647.
+
+    let big = 2147483647 + 1;
+
+Use floats for larger numbers, as in `2147483647.0 + 1`. Int arithmetic can also be made to \
+wrap around or saturate instead of failing, with `--overflow=wrapping` or \
+`--overflow=saturating`.",
+    },
+    ErrorCode {
+        code: "E0015",
+        summary: "recursion limit reached",
+        explanation: "\
+A function call was nested in too many other calls, which usually means a recursion that never \
+reaches its base case.
+
+    fn count(n) { return count(n + 1); } // nothing stops the recursion
+    count(0);
+
+Make sure every recursive function has a case that returns without calling itself:
+
+    fn count(n) { if n == 10 { return n; } return count(n + 1); }
+
+A recursion that is meant to go this deep can be rewritten as a loop.",
+    },
+    ErrorCode {
+        code: "E0101",
+        summary: "unexpected character",
+        explanation: "\
+The source contains a character that does not start any token of the language, such as `$` or `@`.
+
+Remove the character, or check for a typo in an operator.",
+    },
+    ErrorCode {
+        code: "E0102",
+        summary: "invalid number literal",
+        explanation: "\
+A number literal is malformed or out of range.
+
+    let a = 12ab;        // letters glued to the digits
+    let b = 1e;          // an exponent without digits
+    let c = 99999999999; // larger than 2147483647
+
+Integers are written as decimal digits and must fit in a 32-bit integer. Floats have a fraction \
+(`1.5`), an exponent (`2e-3`) or both. Separate numbers from following names with a space or \
+an operator.",
+    },
+    ErrorCode {
+        code: "E0103",
+        summary: "unterminated string literal",
+        explanation: "\
+A string literal was opened with `\"` but the input ended before its closing quote.
+
+    let a = \"hello;
+
+Add the closing quote. A quote inside the string must be escaped as `\\\"`.",
+    },
+    ErrorCode {
+        code: "E0104",
+        summary: "invalid escape sequence",
+        explanation: "\
+A string literal contains a backslash that does not start a known escape sequence.
+
+    let path = \"C:\\qux\"; // `\\q` is not an escape sequence
+
+The supported escapes are `\\n`, `\\t`, `\\\"`, `\\\\` and `\\u{...}` with hexadecimal digits naming \
+a Unicode scalar value. Write `\\\\` for a literal backslash.",
+    },
+    ErrorCode {
+        code: "E0105",
+        summary: "unterminated block comment",
+        explanation: "\
+A `/*` block comment was opened but the input ended before its closing `*/`.
+
+    /* outer /* inner */
+    let a = 1;
+
+Block comments nest, so every `/*` inside a comment needs its own `*/`.",
+    },
+    ErrorCode {
+        code: "E0201",
+        summary: "unexpected token",
+        explanation: "\
+The parser found a token that cannot appear at this point of a statement.
+
+    let = 1;     // an identifier is expected after `let`
+    let a = 1 2; // a `;` is expected after the value
+
+The message names what the parser expected instead.",
+    },
+    ErrorCode {
+        code: "E0202",
+        summary: "unexpected end of input",
+        explanation: "\
+The input ended in the middle of a statement or expression.
+
+    let a = (1 + 2
+
+Complete the statement, e.g. by closing the parenthesis or giving the missing value.",
+    },
+    ErrorCode {
+        code: "E0203",
+        summary: "invalid assignment target",
+        explanation: "\
+The left-hand side of `=` is not something that can be assigned to.
+
+    1 + 2 = 3;
+
+Only variables, array elements and map entries can be assigned to:
+
+    let a = 1;
+    a = 3;
+    let list = [1, 2];
+    list[0] = 3;
+    let user = { name: \"x\" };
+    user.name = \"y\";
+    user[\"age\"] = 3;",
+    },
+    ErrorCode {
+        code: "E0204",
+        summary: "constant without a value",
+        explanation: "\
+A `const` declaration did not give the constant a value. Unlike variables, constants cannot be \
+declared first and assigned later.
+
+    const a;
+
+Give the constant its value in the declaration:
+
+    const a = 1;",
+    },
+    ErrorCode {
+        code: "E0205",
+        summary: "`break` or `continue` outside of a loop",
+        explanation: "\
+`break` and `continue` can only be used inside the body of a loop.
+
+    let a = 1;
+    break; // there is no loop to leave
+
+Use them inside a `while` loop:
+
+    while a < 10 {
+        if a == 5 { break; }
+        a = a + 1;
+    }",
+    },
+    ErrorCode {
+        code: "E0206",
+        summary: "`return` outside of a function",
+        explanation: "\
+`return` can only be used inside the body of a function.
+
+    let a = 1;
+    return a; // there is no function to return from
+
+Move the code into a function and call it:
+
+    fn compute() {
+        let a = 1;
+        return a;
+    }",
+    },
+    ErrorCode {
+        code: "E0207",
+        summary: "nesting too deep",
+        explanation: "\
+Expressions, blocks or operator chains are nested in each other more than 256 levels deep, \
+which is more than the interpreter can handle.
+
+    let a = ((((((((((...1))))))))));
+
+Split the code with variables or functions, so that each part is nested less deeply:
+
+    let inner = (1 + 2) * 3;
+    let a = (inner - 1) * 4;",
+    },
+];
+
+pub fn explain(code: &str) -> Option<&'static ErrorCode> {
+    ERROR_CODES
+        .iter()
+        .find(|error_code| error_code.code.eq_ignore_ascii_case(code))
+}
+
+/// A problem in the source, ready to be rendered for the user.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub code: &'static str,
+    pub message: String,
+    pub span: Span,
+    /// Short text printed next to the underlined source.
+    pub label: Option<String>,
+    pub notes: Vec<String>,
+    pub help: Option<String>,
+}
+
+impl Diagnostic {
+    pub fn error(code: &'static str, message: impl ToString, span: Span) -> Self {
+        Self {
+            code,
+            message: message.to_string(),
+            span,
+            label: None,
+            notes: Vec::new(),
+            help: None,
+        }
+    }
+    pub fn with_label(mut self, label: impl ToString) -> Self {
+        self.label = Some(label.to_string());
+        self
+    }
+    pub fn with_note(mut self, note: impl ToString) -> Self {
+        self.notes.push(note.to_string());
+        self
+    }
+    pub fn with_help(mut self, help: impl ToString) -> Self {
+        self.help = Some(help.to_string());
+        self
+    }
+
+    /// Renders the diagnostic against the `source` it was found in:
+    ///
+    /// ```text
+    /// error[E0001]: Cannot resolve b as it does not exist.
+    ///  --> script.awa:1:9
+    ///   |
+    /// 1 | let a = b + 1;
+    ///   |         ^ not found in this scope
+    /// ```
+    pub fn render(&self, file_name: &str, source: &str, colour: bool) -> String {
+        let style = Style { colour };
+        let line_text = source.lines().nth(self.span.line - 1).unwrap_or("");
+        let line_number = self.span.line.to_string();
+        let gutter = " ".repeat(line_number.len());
+
+        // Keep tabs in the padding so the carets line up with the source line.
+        let padding: String = line_text
+            .chars()
+            .take(self.span.column - 1)
+            .map(|c| if c == '\t' { '\t' } else { ' ' })
+            .collect();
+        let available = line_text
+            .chars()
+            .count()
+            .saturating_sub(self.span.column - 1);
+        let underlined = source
+            .get(self.span.start..self.span.end)
+            .unwrap_or("")
+            .chars()
+            .take_while(|c| *c != '\n')
+            .count()
+            .min(available)
+            .max(1);
+
+        let mut output = format!(
+            "{}{}\n",
+            style.error(&format!("error[{}]", self.code)),
+            style.bold(&format!(": {}", self.message))
+        );
+        output += &format!(
+            "{gutter}{} {file_name}:{}\n",
+            style.accent("-->"),
+            self.span
+        );
+        output += &format!("{gutter} {}\n", style.accent("|"));
+        output += &format!(
+            "{} {line_text}\n",
+            style.accent(&format!("{line_number} |"))
+        );
+        output += &format!(
+            "{gutter} {} {padding}{}",
+            style.accent("|"),
+            style.error(&"^".repeat(underlined))
+        );
+        if let Some(label) = &self.label {
+            output += &format!(" {}", style.error(label));
+        }
+        output += "\n";
+        for note in &self.notes {
+            output += &format!("{gutter} {} note: {note}\n", style.accent("="));
+        }
+        if let Some(help) = &self.help {
+            output += &format!("{gutter} {} help: {help}\n", style.accent("="));
+        }
+        output
+    }
+}
+
+struct Style {
+    colour: bool,
+}
+
+impl Style {
+    fn paint(&self, escape: &str, text: &str) -> String {
+        if self.colour {
+            format!("\x1b[{escape}m{text}\x1b[0m")
+        } else {
+            text.to_string()
+        }
+    }
+    fn error(&self, text: &str) -> String {
+        self.paint("1;31", text)
+    }
+    fn accent(&self, text: &str) -> String {
+        self.paint("1;34", text)
+    }
+    fn bold(&self, text: &str) -> String {
+        self.paint("1", text)
+    }
+}
+
+/// Colour is used when stderr is a terminal, unless `NO_COLOR` is set.
+pub fn use_colour() -> bool {
+    std::io::stderr().is_terminal() && std::env::var_os("NO_COLOR").is_none()
+}
+
+/// Prints every error of `errors` as a diagnostic on stderr.
+pub fn emit<'a, E: 'a>(
+    errors: impl IntoIterator<Item = &'a Spanned<E>>,
+    file_name: &str,
+    source: &str,
+) where
+    Diagnostic: From<&'a Spanned<E>>,
+{
+    emit_at(errors, file_name, source, 0);
+}
+
+/// Like [`emit`], for errors in a `source` that was lexed from `offset`.
+pub fn emit_at<'a, E: 'a>(
+    errors: impl IntoIterator<Item = &'a Spanned<E>>,
+    file_name: &str,
+    source: &str,
+    offset: usize,
+) where
+    Diagnostic: From<&'a Spanned<E>>,
+{
+    let colour = use_colour();
+    for error in errors {
+        let mut diagnostic = Diagnostic::from(error);
+        diagnostic.span.start -= offset;
+        diagnostic.span.end -= offset;
+        eprint!("{}", diagnostic.render(file_name, source, colour));
+    }
+}
+
+impl From<&Spanned<LexError>> for Diagnostic {
+    fn from(error: &Spanned<LexError>) -> Self {
+        match &error.node {
+            LexError::UnexpectedCharacter(_) => Diagnostic::error("E0101", &error.node, error.span)
+                .with_label("not part of the language"),
+            LexError::InvalidNumber(_) => Diagnostic::error("E0102", &error.node, error.span)
+                .with_label("not a valid number")
+                .with_note("numbers are written like `42`, `1.5` or `1e-3`, and integers must fit in 32 bits"),
+            LexError::UnterminatedLiteral => Diagnostic::error("E0103", &error.node, error.span)
+                .with_label("string starts here")
+                .with_help("add the closing `\"`"),
+            LexError::InvalidEscape(_) => Diagnostic::error("E0104", &error.node, error.span)
+                .with_label("in this string")
+                .with_help("use `\\\\` for a literal backslash"),
+            LexError::UnterminatedComment => Diagnostic::error("E0105", &error.node, error.span)
+                .with_label("comment starts here")
+                .with_note("block comments nest, so every `/*` needs its own `*/`"),
+        }
+    }
+}
+
+impl From<&Spanned<ParseError>> for Diagnostic {
+    fn from(error: &Spanned<ParseError>) -> Self {
+        match &error.node {
+            ParseError::UnexpectedToken { expected, .. } => {
+                Diagnostic::error("E0201", &error.node, error.span)
+                    .with_label(format!("expected {expected}"))
+            }
+            ParseError::UnexpectedEof { .. } => {
+                Diagnostic::error("E0202", &error.node, error.span).with_label("input ends here")
+            }
+            ParseError::InvalidAssignmentTarget => {
+                Diagnostic::error("E0203", &error.node, error.span)
+                    .with_label("cannot be assigned to")
+                    .with_help("only variables, array elements and map entries can be assigned to")
+            }
+            ParseError::MissingConstInitializer(identifier) => {
+                Diagnostic::error("E0204", &error.node, error.span)
+                    .with_label("missing value")
+                    .with_help(format!("give it a value: `const {identifier} = ...;`"))
+            }
+            ParseError::OutsideLoop(_) => {
+                Diagnostic::error("E0205", &error.node, error.span).with_label("not inside a loop")
+            }
+            ParseError::OutsideFunction => Diagnostic::error("E0206", &error.node, error.span)
+                .with_label("not inside a function"),
+            ParseError::InvalidNumber(_) => Diagnostic::error("E0102", &error.node, error.span)
+                .with_label("doesn't fit an int")
+                .with_note("ints range from -2147483648 to 2147483647"),
+            ParseError::NestedTooDeep => Diagnostic::error("E0207", &error.node, error.span)
+                .with_label(format!("nested {MAX_NESTING_DEPTH} levels deep here")),
+        }
+    }
+}
+
+impl From<&Spanned<EnvError>> for Diagnostic {
+    fn from(error: &Spanned<EnvError>) -> Self {
+        match &error.node {
+            EnvError::VarNotFound(variable_name) => {
+                Diagnostic::error("E0001", &error.node, error.span)
+                    .with_label("not found in this scope")
+                    .with_help(format!("declare it first: `let {variable_name} = ...;`"))
+            }
+            EnvError::VarRedefining(variable_name) => {
+                Diagnostic::error("E0002", &error.node, error.span)
+                    .with_label("already declared in this scope")
+                    .with_help(format!(
+                        "to change its value, assign to it instead: `{variable_name} = ...;`"
+                    ))
+            }
+            EnvError::ConstAssignment(constant_name) => {
+                Diagnostic::error("E0012", &error.node, error.span)
+                    .with_label("assigned here")
+                    .with_help(format!(
+                        "declare it with `let {constant_name} = ...;` to allow changing it"
+                    ))
+            }
+            EnvError::InvalidOperands { .. } => Diagnostic::error("E0003", &error.node, error.span)
+                .with_label("unsupported operand types"),
+            EnvError::NotIterable(found) => Diagnostic::error("E0006", &error.node, error.span)
+                .with_label(format!("found {found}")),
+            EnvError::ArityMismatch { expected, .. } => {
+                Diagnostic::error("E0007", &error.node, error.span)
+                    .with_label(format!("expected {expected} argument(s)"))
+            }
+            EnvError::NotCallable(found) => Diagnostic::error("E0008", &error.node, error.span)
+                .with_label(format!("found {found}")),
+            EnvError::NegativeIndex(_) => {
+                Diagnostic::error("E0009", &error.node, error.span).with_label("indices start at 0")
+            }
+            EnvError::IndexOutOfBounds { length, .. } => {
+                Diagnostic::error("E0010", &error.node, error.span)
+                    .with_label(format!("the array has {length} element(s)"))
+            }
+            EnvError::MissingKey(_) => {
+                Diagnostic::error("E0011", &error.node, error.span).with_label("key not found")
+            }
+            EnvError::DivisionByZero(_) => Diagnostic::error("E0013", &error.node, error.span)
+                .with_label("the divisor is zero"),
+            EnvError::IntegerOverflow { .. } => Diagnostic::error("E0014", &error.node, error.span)
+                .with_label("the result doesn't fit an int"),
+            EnvError::NonBoolCondition(found) => {
+                Diagnostic::error("E0005", &error.node, error.span)
+                    .with_label(format!("found {found}"))
+                    .with_note("strict mode is on, so conditions are not tested for truthiness")
+            }
+            EnvError::InvalidOperand { operand, .. } => {
+                Diagnostic::error("E0004", &error.node, error.span)
+                    .with_label(format!("found {operand}"))
+            }
+            EnvError::RecursionLimit(_) => Diagnostic::error("E0015", &error.node, error.span)
+                .with_label(format!("call nested {MAX_CALL_DEPTH} calls deep")),
+        }
+    }
+}
@@ -1,35 +1,135 @@
+use std::rc::Rc;
+
+use super::span::Spanned;
+
 #[derive(Debug)]
 pub enum Statement {
+    #[allow(dead_code)]
     Program(Program),
     VarDeclaration {
         identifier: String,
-        value: Option<Expression>,
+        value: Option<Spanned<Expression>>,
     },
     VarAssignment {
-        identifier: String,
-        value: Expression,
+        target: AssignmentTarget,
+        value: Spanned<Expression>,
     },
     ConstDeclaration {
         identifier: String,
-        value: Expression,
+        value: Spanned<Expression>,
+    },
+    /// `if condition { ... } else ...`, where `else_branch` is either a block or another `If`.
+    If {
+        condition: Spanned<Expression>,
+        then_branch: Box<Spanned<Statement>>,
+        else_branch: Option<Box<Spanned<Statement>>>,
+    },
+    While {
+        condition: Spanned<Expression>,
+        body: Box<Spanned<Statement>>,
+    },
+    /// `for variable in iterable { ... }`.
+    For {
+        variable: String,
+        iterable: Spanned<Expression>,
+        body: Box<Spanned<Statement>>,
     },
-    Expression(Expression),
+    Break,
+    Continue,
+    /// `fn name(parameters) { ... }`, whose definition always has a name.
+    FunctionDeclaration(Rc<FunctionDefinition>),
+    /// `return value;`, or `return;` which returns `null`.
+    Return(Option<Spanned<Expression>>),
+    /// `{ ... }`, evaluated in its own scope.
+    Block(Vec<Spanned<Statement>>),
+    Expression(Spanned<Expression>),
 }
 
 #[derive(Debug)]
+#[allow(clippy::enum_variant_names)]
 pub enum Expression {
     NumericLiteral(i32),
+    FloatLiteral(f64),
+    StringLiteral(String),
+    BoolLiteral(bool),
+    NullLiteral,
     Identifier(String),
+    /// `-`, `+` or `!` applied to a single operand.
+    UnaryExpression {
+        operand: Box<Spanned<Expression>>,
+        operator: String,
+    },
+    /// `start..end`, or `start..=end` when `inclusive`.
+    Range {
+        start: Box<Spanned<Expression>>,
+        end: Box<Spanned<Expression>>,
+        inclusive: bool,
+    },
     BinaryExpression {
-        left: Box<Expression>,
-        right: Box<Expression>,
+        left: Box<Spanned<Expression>>,
+        right: Box<Spanned<Expression>>,
         operator: String,
     },
+    /// An anonymous function, `fn(parameters) { ... }` or `parameter => value`.
+    Function(Rc<FunctionDefinition>),
+    /// `[elements]`.
+    ArrayLiteral(Vec<Spanned<Expression>>),
+    /// `target[index]`, on arrays and maps.
+    Index {
+        target: Box<Spanned<Expression>>,
+        index: Box<Spanned<Expression>>,
+    },
+    /// `{ key: value, ... }`, where keys are identifiers or strings.
+    MapLiteral(Vec<(String, Spanned<Expression>)>),
+    /// `target.field`.
+    Member {
+        target: Box<Spanned<Expression>>,
+        field: String,
+    },
+    /// `callee(arguments)`.
+    Call {
+        callee: Box<Spanned<Expression>>,
+        arguments: Vec<Spanned<Expression>>,
+    },
+}
+
+/// What the left-hand side of `=` can be.
+#[derive(Debug)]
+pub enum AssignmentTarget {
+    /// `variable = value`.
+    Variable(String),
+    /// `target[index] = value`.
+    Index {
+        target: Spanned<Expression>,
+        index: Spanned<Expression>,
+    },
+    /// `target.field = value`.
+    Member {
+        target: Spanned<Expression>,
+        field: String,
+    },
 }
 
+/// A function as written in the source.
+/// It is shared with the function values created from it, which outlive the AST.
 #[derive(Debug)]
+pub struct FunctionDefinition {
+    /// `None` for anonymous functions.
+    pub name: Option<String>,
+    pub parameters: Vec<String>,
+    pub body: Vec<Spanned<Statement>>,
+}
+
+impl FunctionDefinition {
+    /// The name shown in error messages.
+    pub fn display_name(&self) -> &str {
+        self.name.as_deref().unwrap_or("<anonymous>")
+    }
+}
+
+#[derive(Debug, Default)]
 pub struct Program {
-    pub body: Vec<Statement>,
+    pub body: Vec<Spanned<Statement>>,
 }
 
 impl Program {
@@ -1,27 +1,101 @@
 use std::{collections::VecDeque, fmt::Display};
 
+use super::span::{Span, Spanned};
+
 /// # Note: following rust document "Defining an Enum"
 /// As the document says:
 /// > However, representing the same concept using just an enum is more concise: rather than an
 /// > enum inside a struct, we can put data directly into each enum variant.
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Token {
     Number(String),
+    Float(String),
+    /// The contents of a string literal, with escape sequences already resolved.
+    String(String),
     Identifier(String),
     Equals,
+    /// `!`, the logical not.
+    Bang,
     OpenParen,
     CloseParen,
+    OpenBrace,
+    CloseBrace,
+    OpenBracket,
+    CloseBracket,
+    Comma,
+    Colon,
+    /// `.`, for field access.
+    Dot,
+    /// `=>`, between the parameter and the body of a short function.
+    Arrow,
+    /// `..`, the exclusive range.
+    DotDot,
+    /// `..=`, the inclusive range.
+    DotDotEquals,
     BinaryOperator(BinaryOperator),
     Let,
     Const,
+    If,
+    Else,
+    While,
+    For,
+    In,
+    Fn,
+    Return,
+    Break,
+    Continue,
+    True,
+    False,
+    Null,
     Semicolon,
-    EOF,
+    Eof,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum BinaryOperator {
     Additive(String),
     Multiplicitave(String),
+    /// `<`, `<=`, `>` and `>=`.
+    Comparison(String),
+    /// `==` and `!=`.
+    Equality(String),
+    LogicalAnd(String),
+    LogicalOr(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum LexError {
+    UnexpectedCharacter(char),
+    InvalidNumber(String),
+    /// A string literal without its closing quote.
+    UnterminatedLiteral,
+    /// An unknown or malformed escape sequence in a string literal, e.g. `\q` or `\u{110000}`.
+    InvalidEscape(String),
+    /// A `/*` comment without its matching `*/`.
+    UnterminatedComment,
+}
+
+impl LexError {
+    /// Whether the error only comes from the source ending too early, so that more source could
+    /// fix it. Strings and block comments may span lines, so they are only unterminated at the
+    /// end of the source.
+    pub fn is_incomplete_input(&self) -> bool {
+        matches!(self, Self::UnterminatedLiteral | Self::UnterminatedComment)
+    }
+}
+
+impl Display for LexError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::UnexpectedCharacter(character) => {
+                write!(f, "Unexpected character: {character:?}")
+            }
+            Self::InvalidNumber(number) => write!(f, "Invalid number literal: {number}"),
+            Self::UnterminatedLiteral => write!(f, "Unterminated string literal."),
+            Self::InvalidEscape(escape) => write!(f, "Invalid escape sequence: {escape}"),
+            Self::UnterminatedComment => write!(f, "Unterminated block comment."),
+        }
+    }
 }
 
 impl Display for BinaryOperator {
@@ -32,6 +106,10 @@ impl Display for BinaryOperator {
             match self {
                 Self::Additive(string) => string,
                 Self::Multiplicitave(string) => string,
+                Self::Comparison(string) => string,
+                Self::Equality(string) => string,
+                Self::LogicalAnd(string) => string,
+                Self::LogicalOr(string) => string,
             }
         )
     }
@@ -44,54 +122,146 @@ impl Display for Token {
             "{}",
             match self {
                 Self::Number(string) => string.clone(),
+                Self::Float(string) => string.clone(),
+                Self::String(string) => format!("{string:?}"),
                 Self::Identifier(string) => string.clone(),
                 Self::Equals => "=".to_string(),
+                Self::Bang => "!".to_string(),
                 Self::OpenParen => "(".to_string(),
                 Self::CloseParen => ")".to_string(),
+                Self::OpenBrace => "{".to_string(),
+                Self::CloseBrace => "}".to_string(),
+                Self::OpenBracket => "[".to_string(),
+                Self::CloseBracket => "]".to_string(),
+                Self::Comma => ",".to_string(),
+                Self::Colon => ":".to_string(),
+                Self::Dot => ".".to_string(),
+                Self::Arrow => "=>".to_string(),
+                Self::DotDot => "..".to_string(),
+                Self::DotDotEquals => "..=".to_string(),
                 Self::BinaryOperator(binary_operator) => binary_operator.to_string(),
                 Self::Let => "let".to_string(),
                 Self::Const => "const".to_string(),
+                Self::If => "if".to_string(),
+                Self::Else => "else".to_string(),
+                Self::While => "while".to_string(),
+                Self::For => "for".to_string(),
+                Self::In => "in".to_string(),
+                Self::Fn => "fn".to_string(),
+                Self::Return => "return".to_string(),
+                Self::Break => "break".to_string(),
+                Self::Continue => "continue".to_string(),
+                Self::True => "true".to_string(),
+                Self::False => "false".to_string(),
+                Self::Null => "null".to_string(),
                 Self::Semicolon => ";".to_string(),
-                Self::EOF => "<END OF FILE>".to_string(),
+                Self::Eof => "<END OF FILE>".to_string(),
             }
         )
     }
 }
 
+/// The reserved words and their tokens, as looked up by `find_reserved` and completed in the
+/// shell.
+pub const KEYWORDS: [(&str, Token); 14] = [
+    ("let", Token::Let),
+    ("const", Token::Const),
+    ("if", Token::If),
+    ("else", Token::Else),
+    ("while", Token::While),
+    ("for", Token::For),
+    ("in", Token::In),
+    ("fn", Token::Fn),
+    ("return", Token::Return),
+    ("break", Token::Break),
+    ("continue", Token::Continue),
+    ("true", Token::True),
+    ("false", Token::False),
+    ("null", Token::Null),
+];
+
 /// # Note: To use `String` as `&str`
 /// It's actually quite easy...just use `String`'s `as_str`.
 /// ```
 /// let string = String::new("awa");
 /// let static_str = string.as_str(); // type = &'static str
 /// ```
-pub fn find_reserved(token: &String) -> Option<Token> {
-    match token.as_str() {
-        "let" => Some(Token::Let),
-        "const" => Some(Token::Const),
-        _ => None,
-    }
+pub fn find_reserved(token: &str) -> Option<Token> {
+    KEYWORDS
+        .into_iter()
+        .find_map(|(keyword, reserved)| (keyword == token).then_some(reserved))
 }
 
 pub fn is_skippable(character: &char) -> bool {
-    vec![' ', '\n', '\t'].contains(character)
+    [' ', '\n', '\t', '\r'].contains(character)
 }
 
 fn is_legal_identifier_character(character: &char) -> bool {
-    character.is_alphabetic() || vec!['_'].contains(character)
+    character.is_alphabetic() || ['_'].contains(character)
 }
 
 fn is_additive(character: &char) -> bool {
-    vec!['+', '-'].contains(character)
+    ['+', '-'].contains(character)
 }
 
 fn is_multiplicitave(character: &char) -> bool {
-    vec!['*', '/', '%'].contains(character)
+    ['*', '/', '%'].contains(character)
+}
+
+/// The remaining characters of the source, along with the position of the first one.
+struct Cursor {
+    characters: VecDeque<char>,
+    offset: usize,
+    line: usize,
+    column: usize,
+}
+
+impl Cursor {
+    fn new(source_code: &str, offset: usize) -> Self {
+        Self {
+            characters: source_code.chars().collect(),
+            offset,
+            line: 1,
+            column: 1,
+        }
+    }
+    fn is_empty(&self) -> bool {
+        self.characters.is_empty()
+    }
+    fn peek(&self) -> Option<char> {
+        self.characters.front().copied()
+    }
+    fn peek_nth(&self, n: usize) -> Option<char> {
+        self.characters.get(n).copied()
+    }
+    fn pop_front(&mut self) -> Option<char> {
+        let character = self.characters.pop_front()?;
+        self.offset += character.len_utf8();
+        if character == '\n' {
+            self.line += 1;
+            self.column = 1;
+        } else {
+            self.column += 1;
+        }
+        Some(character)
+    }
+    /// A zero-width span at the current position.
+    fn here(&self) -> Span {
+        Span::new(self.offset, self.offset, self.line, self.column)
+    }
+    /// The span from `start` up to the current position.
+    fn since(&self, start: Span) -> Span {
+        Span::new(start.start, self.offset, start.line, start.column)
+    }
 }
 
-fn compose_identifier(head: char, characters: &mut VecDeque<char>) -> Token {
+fn compose_identifier(head: char, characters: &mut Cursor) -> Token {
     let mut identifier = String::from(head);
-    while !characters.is_empty() && is_legal_identifier_character(&characters[0]) {
-        identifier += &characters.pop_front().unwrap().to_string();
+    while characters
+        .peek()
+        .is_some_and(|c| is_legal_identifier_character(&c))
+    {
+        identifier.push(characters.pop_front().unwrap());
     }
     match find_reserved(&identifier) {
         Some(t) => t,
@@ -99,47 +269,258 @@ fn compose_identifier(head: char, characters: &mut VecDeque<char>) -> Token {
     }
 }
 
-fn compose_number_token(head: char, characters: &mut VecDeque<char>) -> Token {
+fn compose_digits(number_token: &mut String, characters: &mut Cursor) {
+    while characters.peek().is_some_and(|c| c.is_ascii_digit()) {
+        number_token.push(characters.pop_front().unwrap());
+    }
+}
+
+/// Integers are plain runs of digits fitting an `i32`. Floats additionally have a fraction
+/// (`1.5`), an exponent (`1e-3`) or both; a dot is only part of the number when a digit follows
+/// it, so `1..2` stays a range.
+/// Letters glued to the number (`12ab`) are consumed as part of the bad literal so that lexing
+/// resumes after it.
+fn compose_number_token(head: char, characters: &mut Cursor) -> Result<Token, LexError> {
     let mut number_token = String::from(head);
-    while !characters.is_empty() && characters[0].is_digit(10) {
-        number_token += &characters.pop_front().unwrap().to_string();
+    let mut is_float = false;
+    compose_digits(&mut number_token, characters);
+    if characters.peek() == Some('.') && characters.peek_nth(1).is_some_and(|c| c.is_ascii_digit())
+    {
+        is_float = true;
+        number_token.push(characters.pop_front().unwrap());
+        compose_digits(&mut number_token, characters);
+    }
+    if matches!(characters.peek(), Some('e' | 'E')) {
+        let sign = usize::from(matches!(characters.peek_nth(1), Some('+' | '-')));
+        if characters
+            .peek_nth(1 + sign)
+            .is_some_and(|c| c.is_ascii_digit())
+        {
+            is_float = true;
+            for _ in 0..=sign {
+                number_token.push(characters.pop_front().unwrap());
+            }
+            compose_digits(&mut number_token, characters);
+        }
+    }
+    let mut is_valid = true;
+    while characters
+        .peek()
+        .is_some_and(|c| c.is_ascii_digit() || is_legal_identifier_character(&c))
+    {
+        is_valid = false;
+        number_token.push(characters.pop_front().unwrap());
+    }
+    if is_float {
+        is_valid &= number_token.parse::<f64>().is_ok_and(f64::is_finite);
+    } else {
+        // `2147483648` only fits an int after a unary `-`, which the parser checks.
+        is_valid &= number_token
+            .parse::<u32>()
+            .is_ok_and(|number| number <= i32::MIN.unsigned_abs());
+    }
+    match (is_valid, is_float) {
+        (false, _) => Err(LexError::InvalidNumber(number_token)),
+        (true, false) => Ok(Token::Number(number_token)),
+        (true, true) => Ok(Token::Float(number_token)),
     }
-    Token::Number(number_token)
 }
 
-fn compose_token(characters: &mut VecDeque<char>) -> Option<Token> {
-    Some(match characters.pop_front().unwrap() {
+/// Reads an escape sequence, the backslash being already consumed.
+fn compose_escape(characters: &mut Cursor) -> Result<char, LexError> {
+    let Some(head) = characters.pop_front() else {
+        return Err(LexError::UnterminatedLiteral);
+    };
+    match head {
+        'n' => Ok('\n'),
+        't' => Ok('\t'),
+        '"' => Ok('"'),
+        '\\' => Ok('\\'),
+        'u' => {
+            let mut escape = String::from("\\u");
+            if characters.peek() != Some('{') {
+                return Err(LexError::InvalidEscape(escape));
+            }
+            escape.push(characters.pop_front().unwrap());
+            while characters.peek().is_some_and(|c| c.is_ascii_hexdigit()) {
+                escape.push(characters.pop_front().unwrap());
+            }
+            if characters.peek() != Some('}') {
+                return Err(LexError::InvalidEscape(escape));
+            }
+            escape.push(characters.pop_front().unwrap());
+            u32::from_str_radix(&escape[3..escape.len() - 1], 16)
+                .ok()
+                .and_then(char::from_u32)
+                .ok_or(LexError::InvalidEscape(escape))
+        }
+        c => Err(LexError::InvalidEscape(format!("\\{c}"))),
+    }
+}
+
+/// Reads a double-quoted string literal, the opening quote being already consumed.
+/// After a bad escape sequence the rest of the literal is still consumed, so that lexing resumes
+/// after the closing quote.
+fn compose_string_token(characters: &mut Cursor) -> Result<Token, LexError> {
+    let mut string = String::new();
+    let mut error = None;
+    loop {
+        match characters.pop_front() {
+            None => return Err(LexError::UnterminatedLiteral),
+            Some('"') => break,
+            Some('\\') => match compose_escape(characters) {
+                Ok(c) => string.push(c),
+                Err(LexError::UnterminatedLiteral) => return Err(LexError::UnterminatedLiteral),
+                Err(e) => {
+                    error.get_or_insert(e);
+                }
+            },
+            Some(c) => string.push(c),
+        }
+    }
+    match error {
+        Some(error) => Err(error),
+        None => Ok(Token::String(string)),
+    }
+}
+
+/// Pops the next character if it is `expected`.
+fn followed_by(characters: &mut Cursor, expected: char) -> bool {
+    if characters.peek() == Some(expected) {
+        characters.pop_front();
+        return true;
+    }
+    false
+}
+
+/// Reads the operators made of several characters, the first one being already consumed.
+/// Returns `None` if `head` doesn't start one.
+fn compose_operator(head: char, characters: &mut Cursor) -> Option<Token> {
+    if head == '.' && followed_by(characters, '.') {
+        if followed_by(characters, '=') {
+            return Some(Token::DotDotEquals);
+        }
+        return Some(Token::DotDot);
+    }
+    if head == '=' && followed_by(characters, '>') {
+        return Some(Token::Arrow);
+    }
+    let operator = match head {
+        '=' if followed_by(characters, '=') => BinaryOperator::Equality("==".to_string()),
+        '!' if followed_by(characters, '=') => BinaryOperator::Equality("!=".to_string()),
+        '<' | '>' if followed_by(characters, '=') => BinaryOperator::Comparison(format!("{head}=")),
+        '<' | '>' => BinaryOperator::Comparison(head.to_string()),
+        '&' if followed_by(characters, '&') => BinaryOperator::LogicalAnd("&&".to_string()),
+        '|' if followed_by(characters, '|') => BinaryOperator::LogicalOr("||".to_string()),
+        _ => return None,
+    };
+    Some(Token::BinaryOperator(operator))
+}
+
+/// Skips a `//` comment up to the end of the line, the first slash being already consumed.
+fn skip_line_comment(characters: &mut Cursor) {
+    while characters.peek().is_some_and(|c| c != '\n') {
+        characters.pop_front();
+    }
+}
+
+/// Skips a `/* ... */` comment, the first slash being already consumed.
+/// Block comments nest, so `/* a /* b */ c */` is a single comment.
+fn skip_block_comment(characters: &mut Cursor) -> Result<(), LexError> {
+    characters.pop_front();
+    let mut depth = 1;
+    while depth > 0 {
+        match characters.pop_front() {
+            None => return Err(LexError::UnterminatedComment),
+            Some('/') if characters.peek() == Some('*') => {
+                characters.pop_front();
+                depth += 1;
+            }
+            Some('*') if characters.peek() == Some('/') => {
+                characters.pop_front();
+                depth -= 1;
+            }
+            Some(_) => {}
+        }
+    }
+    Ok(())
+}
+
+/// Returns `Ok(None)` for characters that don't produce a token, such as whitespace and comments.
+fn compose_token(characters: &mut Cursor) -> Result<Option<Token>, LexError> {
+    let Some(head) = characters.pop_front() else {
+        return Ok(None);
+    };
+    if let Some(token) = compose_operator(head, characters) {
+        return Ok(Some(token));
+    }
+    Ok(Some(match head {
         '(' => Token::OpenParen,
         ')' => Token::CloseParen,
+        '{' => Token::OpenBrace,
+        '}' => Token::CloseBrace,
+        '[' => Token::OpenBracket,
+        ']' => Token::CloseBracket,
+        ',' => Token::Comma,
+        ':' => Token::Colon,
+        '.' => Token::Dot,
         '=' => Token::Equals,
         ';' => Token::Semicolon,
+        '!' => Token::Bang,
+        '"' => compose_string_token(characters)?,
+        '/' if characters.peek() == Some('/') => {
+            skip_line_comment(characters);
+            return Ok(None);
+        }
+        '/' if characters.peek() == Some('*') => {
+            skip_block_comment(characters)?;
+            return Ok(None);
+        }
         c if is_additive(&c) => Token::BinaryOperator(BinaryOperator::Additive(c.to_string())),
         c if is_multiplicitave(&c) => {
             Token::BinaryOperator(BinaryOperator::Multiplicitave(c.to_string()))
         }
-        c if is_skippable(&c) => return None,
-        c if c.is_digit(10) => compose_number_token(c, characters),
+        c if is_skippable(&c) => return Ok(None),
+        c if c.is_ascii_digit() => compose_number_token(c, characters)?,
         c if is_legal_identifier_character(&c) => compose_identifier(c, characters),
-        _ => {
-            panic!(
-                "Undefined character: {c}",
-                c = characters.pop_front().unwrap().to_string()
-            );
-        }
-    })
+        c => return Err(LexError::UnexpectedCharacter(c)),
+    }))
 }
 
-pub fn tokenize(source_code: String) -> Vec<Token> {
-    let mut tokens: Vec<Token> = Vec::new();
-    let mut src: VecDeque<char> = source_code.chars().collect();
+/// Splits the source into tokens, always ending with [`Token::Eof`].
+///
+/// Lexing carries on past a bad character or literal, so every lexical problem of the source is
+/// reported at once.
+pub fn tokenize(source_code: String) -> Result<Vec<Spanned<Token>>, Vec<Spanned<LexError>>> {
+    tokenize_at(source_code, 0)
+}
+
+/// Like [`tokenize`], with byte offsets in spans starting from `offset` instead of 0.
+pub fn tokenize_at(
+    source_code: String,
+    offset: usize,
+) -> Result<Vec<Spanned<Token>>, Vec<Spanned<LexError>>> {
+    let mut tokens: Vec<Spanned<Token>> = Vec::new();
+    let mut errors: Vec<Spanned<LexError>> = Vec::new();
+    let mut src = Cursor::new(&source_code, offset);
+    // The end of input is placed right after the last token, so that errors about it don't point
+    // at trailing blank lines or comments.
+    let mut end = src.here();
 
     while !src.is_empty() {
-        if let Some(token) = compose_token(&mut src) {
-            tokens.push(token);
-        } else {
-            continue;
+        let start = src.here();
+        match compose_token(&mut src) {
+            Ok(Some(token)) => {
+                tokens.push(Spanned::new(token, src.since(start)));
+                end = src.here();
+            }
+            Ok(None) => {}
+            Err(error) => errors.push(Spanned::new(error, src.since(start))),
         }
     }
-    tokens.push(Token::EOF);
-    tokens
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+    tokens.push(Spanned::new(Token::Eof, end));
+    Ok(tokens)
 }
@@ -1,3 +1,4 @@
 pub mod ast;
 pub mod lexer;
 pub mod parser;
+pub mod span;
@@ -1,169 +1,806 @@
-use std::collections::VecDeque;
+use std::{collections::VecDeque, fmt::Display, rc::Rc};
 
 use super::{
-    ast::{Expression, Program, Statement},
-    lexer::{tokenize, BinaryOperator, Token},
+    ast::{AssignmentTarget, Expression, FunctionDefinition, Program, Statement},
+    lexer::{tokenize_at, BinaryOperator, LexError, Token},
+    span::{Span, Spanned},
 };
 
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParseError {
+    /// `expected` describes what would have been accepted, e.g. `"';'"` or `"an expression"`.
+    UnexpectedToken {
+        expected: String,
+        found: Token,
+    },
+    UnexpectedEof {
+        expected: String,
+    },
+    InvalidAssignmentTarget,
+    MissingConstInitializer(String),
+    /// `break` or `continue` used outside of a loop.
+    OutsideLoop(Token),
+    /// `return` used outside of a function.
+    OutsideFunction,
+    /// Expressions or statements nested more than [`MAX_NESTING_DEPTH`] deep.
+    NestedTooDeep,
+    /// `2147483648`, which the lexer accepts for `-2147483648`, anywhere else.
+    InvalidNumber(String),
+}
+
+impl ParseError {
+    /// Whether the error only comes from the source ending too early, so that more source could
+    /// fix it.
+    pub fn is_incomplete_input(&self) -> bool {
+        matches!(self, Self::UnexpectedEof { .. })
+    }
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::UnexpectedToken { expected, found } => {
+                write!(f, "Expected {expected}, but '{found}' found.")
+            }
+            Self::UnexpectedEof { expected } => {
+                write!(f, "Expected {expected}, but the input ended.")
+            }
+            Self::InvalidAssignmentTarget => write!(f, "Invalid assignment target."),
+            Self::MissingConstInitializer(identifier) => {
+                write!(
+                    f,
+                    "Constant declaration of {identifier} should contain a value."
+                )
+            }
+            Self::OutsideLoop(keyword) => write!(f, "'{keyword}' outside of a loop."),
+            Self::OutsideFunction => write!(f, "'return' outside of a function."),
+            Self::NestedTooDeep => write!(
+                f,
+                "Expressions and blocks are nested more than {MAX_NESTING_DEPTH} deep."
+            ),
+            Self::InvalidNumber(number) => write!(f, "Invalid number literal: {number}"),
+        }
+    }
+}
+
+type ParseResult<T> = Result<T, Spanned<ParseError>>;
+
+/// How deep expressions and statements can be nested in each other. Parsing, evaluating and
+/// printing them all recurse on the nesting, so deeper code could overflow the stack.
+pub const MAX_NESTING_DEPTH: usize = 256;
+
 #[derive(Debug)]
 pub struct Parser {
-    tokens: VecDeque<Token>,
+    tokens: VecDeque<Spanned<Token>>,
+    /// Errors recovered from so far.
+    errors: Vec<Spanned<ParseError>>,
+    /// How many loops enclose the statement being parsed.
+    loop_depth: usize,
+    /// How many functions enclose the statement being parsed.
+    function_depth: usize,
+    /// How many expressions and statements enclose the one being parsed.
+    nesting_depth: usize,
 }
 
 impl Parser {
-    pub fn initialize(source_code: String) -> Self {
-        Self {
-            tokens: VecDeque::from(tokenize(source_code)),
-        }
+    pub fn initialize(source_code: String) -> Result<Self, Vec<Spanned<LexError>>> {
+        Self::initialize_at(source_code, 0)
+    }
+    /// Like [`Parser::initialize`], with byte offsets in spans starting from `offset`.
+    pub fn initialize_at(
+        source_code: String,
+        offset: usize,
+    ) -> Result<Self, Vec<Spanned<LexError>>> {
+        Ok(Self {
+            tokens: VecDeque::from(tokenize_at(source_code, offset)?),
+            errors: Vec::new(),
+            loop_depth: 0,
+            function_depth: 0,
+            nesting_depth: 0,
+        })
     }
     fn eof(&self) -> bool {
-        self.tokens[0] == Token::EOF
+        self.tokens[0].node == Token::Eof
     }
     fn at(&self) -> &Token {
-        &self.tokens[0]
+        &self.tokens[0].node
     }
     /// # Caution
     /// The Token Vec should always be not empty.
-    /// Also, the last token should always be **EOF**.
-    fn pop_front(&mut self) -> Token {
+    /// Also, the last token should always be **EOF**, which is never popped.
+    fn pop_front(&mut self) -> Spanned<Token> {
+        if self.eof() {
+            return self.tokens[0].clone();
+        }
         self.tokens.pop_front().unwrap()
     }
-    fn parse_additive_expression(&mut self) -> Expression {
-        let mut left = self.parse_multiplicitave_expression();
-
-        while matches!(
-            self.at(),
-            Token::BinaryOperator(BinaryOperator::Additive(_))
-        ) {
-            let operator = self.pop_front().to_string();
-            let right = self.parse_multiplicitave_expression();
-            left = Expression::BinaryExpression {
-                left: Box::new(left),
-                right: Box::new(right),
-                operator,
-            }
+    /// The error for finding the current token where `expected` should be.
+    fn unexpected(&self, expected: &str) -> Spanned<ParseError> {
+        let Spanned { node: token, span } = &self.tokens[0];
+        let expected = expected.to_string();
+        let error = match token {
+            Token::Eof => ParseError::UnexpectedEof { expected },
+            found => ParseError::UnexpectedToken {
+                expected,
+                found: found.clone(),
+            },
+        };
+        Spanned::new(error, *span)
+    }
+    /// Pops the current token if it is `token`.
+    fn expect(&mut self, token: Token, expected: &str) -> ParseResult<Span> {
+        if *self.at() != token {
+            return Err(self.unexpected(expected));
         }
-        left
+        Ok(self.pop_front().span)
     }
-    fn parse_multiplicitave_expression(&mut self) -> Expression {
-        let mut left = self.parse_primary_expression();
-        while matches!(
-            self.at(),
-            Token::BinaryOperator(BinaryOperator::Multiplicitave(_))
-        ) {
-            let operator = self.pop_front().to_string();
-            let right = self.parse_primary_expression();
-            left = Expression::BinaryExpression {
-                left: Box::new(left),
-                right: Box::new(right),
-                operator,
+    fn expect_identifier(&mut self, expected: &str) -> ParseResult<Spanned<String>> {
+        if !matches!(self.at(), Token::Identifier(_)) {
+            return Err(self.unexpected(expected));
+        }
+        match self.pop_front() {
+            Spanned {
+                node: Token::Identifier(identifier),
+                span,
+            } => Ok(Spanned::new(identifier, span)),
+            _ => unreachable!(),
+        }
+    }
+    /// Statements end with `;`, which may be left out before the end of the input or of a block.
+    /// Returns `span` extended over the semicolon, if any.
+    fn expect_statement_end(&mut self, span: Span) -> ParseResult<Span> {
+        match self.at() {
+            Token::Semicolon => Ok(span.to(self.pop_front().span)),
+            Token::Eof | Token::CloseBrace => Ok(span),
+            _ => Err(self.unexpected("';'")),
+        }
+    }
+    /// Runs `parse` one level of nesting deeper, failing instead past [`MAX_NESTING_DEPTH`].
+    fn nested<T>(&mut self, parse: impl FnOnce(&mut Self) -> ParseResult<T>) -> ParseResult<T> {
+        if self.nesting_depth >= MAX_NESTING_DEPTH {
+            let span = self.tokens[0].span;
+            self.skip_group();
+            return Err(Spanned::new(ParseError::NestedTooDeep, span));
+        }
+        self.nesting_depth += 1;
+        let result = parse(self);
+        self.nesting_depth -= 1;
+        result
+    }
+    /// Skips the bracketed group starting at the current token, if any, so that the enclosing
+    /// levels find their closing brackets and only one error is reported for deep nesting.
+    fn skip_group(&mut self) {
+        let mut depth = 0;
+        while !self.eof() {
+            match self.at() {
+                Token::OpenParen | Token::OpenBracket | Token::OpenBrace => depth += 1,
+                Token::CloseParen | Token::CloseBracket | Token::CloseBrace => depth -= 1,
+                _ if depth == 0 => return,
+                _ => {}
+            }
+            if depth < 0 {
+                return;
+            }
+            self.pop_front();
+            if depth == 0 {
+                return;
             }
         }
-        left
     }
-    fn parse_primary_expression(&mut self) -> Expression {
-        let token = self.pop_front();
-        match token {
-            Token::Identifier(value) => Expression::Identifier(value),
-            Token::Number(value) => Expression::NumericLiteral(value.parse::<i32>().unwrap()),
-            Token::OpenParen => {
-                let expr = self.parse_expression();
-                assert!(
-                    self.pop_front() == Token::CloseParen,
-                    "syntax error: Expected a close parenthesis"
-                );
-                expr
+    /// Skips tokens until the start of what is likely the next statement, so that parsing can
+    /// carry on after an error.
+    fn synchronize(&mut self) {
+        while !self.eof() {
+            if matches!(
+                self.at(),
+                Token::Let
+                    | Token::Const
+                    | Token::If
+                    | Token::While
+                    | Token::For
+                    | Token::Fn
+                    | Token::Return
+                    | Token::Break
+                    | Token::Continue
+                    | Token::CloseBrace
+            ) {
+                return;
             }
-            Token::CloseParen => {
-                panic!("syntax error: A close parenthesis is missing a corresponding open one.")
+            if self.pop_front().node == Token::Semicolon {
+                return;
             }
-            _ => panic!("Not an expression token: {token}"),
         }
     }
-    fn parse_let(&mut self) -> Statement {
-        assert!(self.pop_front() == Token::Let);
-        let identifier = match self.pop_front() {
-            Token::Identifier(i) => i,
-            _ => panic!("syntax error: constant statement should be followed by an identifier."),
+    /// Parses a left-associative chain of `operand (operator operand)*`, where `is_operator`
+    /// selects the operators of this precedence level.
+    fn parse_binary_expression(
+        &mut self,
+        is_operator: fn(&BinaryOperator) -> bool,
+        parse_operand: fn(&mut Self) -> ParseResult<Spanned<Expression>>,
+    ) -> ParseResult<Spanned<Expression>> {
+        let mut left = parse_operand(self)?;
+        let nesting_depth = self.nesting_depth;
+        while matches!(self.at(), Token::BinaryOperator(operator) if is_operator(operator)) {
+            let operator = self.pop_front().node.to_string();
+            // The chain so far becomes the left operand, one level deeper.
+            self.nesting_depth += 1;
+            let right = match self.nested(parse_operand) {
+                Ok(right) => right,
+                Err(error) => {
+                    self.nesting_depth = nesting_depth;
+                    return Err(error);
+                }
+            };
+            let span = left.span.to(right.span);
+            left = Spanned::new(
+                Expression::BinaryExpression {
+                    left: Box::new(left),
+                    right: Box::new(right),
+                    operator,
+                },
+                span,
+            )
+        }
+        self.nesting_depth = nesting_depth;
+        Ok(left)
+    }
+    fn parse_logical_or_expression(&mut self) -> ParseResult<Spanned<Expression>> {
+        self.parse_binary_expression(
+            |operator| matches!(operator, BinaryOperator::LogicalOr(_)),
+            Self::parse_logical_and_expression,
+        )
+    }
+    fn parse_logical_and_expression(&mut self) -> ParseResult<Spanned<Expression>> {
+        self.parse_binary_expression(
+            |operator| matches!(operator, BinaryOperator::LogicalAnd(_)),
+            Self::parse_equality_expression,
+        )
+    }
+    fn parse_equality_expression(&mut self) -> ParseResult<Spanned<Expression>> {
+        self.parse_binary_expression(
+            |operator| matches!(operator, BinaryOperator::Equality(_)),
+            Self::parse_comparison_expression,
+        )
+    }
+    fn parse_comparison_expression(&mut self) -> ParseResult<Spanned<Expression>> {
+        self.parse_binary_expression(
+            |operator| matches!(operator, BinaryOperator::Comparison(_)),
+            Self::parse_range_expression,
+        )
+    }
+    /// Ranges don't chain: `a..b..c` is an error.
+    fn parse_range_expression(&mut self) -> ParseResult<Spanned<Expression>> {
+        let start = self.parse_additive_expression()?;
+        let inclusive = match self.at() {
+            Token::DotDot => false,
+            Token::DotDotEquals => true,
+            _ => return Ok(start),
         };
-        match self.pop_front() {
-            Token::Semicolon => {
-                Statement::VarDeclaration { identifier, value: None }
+        self.pop_front();
+        let end = self.parse_additive_expression()?;
+        let span = start.span.to(end.span);
+        Ok(Spanned::new(
+            Expression::Range {
+                start: Box::new(start),
+                end: Box::new(end),
+                inclusive,
             },
-            Token::Equals => {
-                let value = Some(self.parse_expression());
-                if self.pop_front() != Token::Semicolon {
-                    panic!("syntax error: Constant declaration statement must end with semicolon.")
-                }
-                Statement::VarDeclaration { identifier, value }
+            span,
+        ))
+    }
+    fn parse_additive_expression(&mut self) -> ParseResult<Spanned<Expression>> {
+        self.parse_binary_expression(
+            |operator| matches!(operator, BinaryOperator::Additive(_)),
+            Self::parse_multiplicitave_expression,
+        )
+    }
+    fn parse_multiplicitave_expression(&mut self) -> ParseResult<Spanned<Expression>> {
+        self.parse_binary_expression(
+            |operator| matches!(operator, BinaryOperator::Multiplicitave(_)),
+            Self::parse_unary_expression,
+        )
+    }
+    fn parse_unary_expression(&mut self) -> ParseResult<Spanned<Expression>> {
+        if !matches!(
+            self.at(),
+            Token::Bang | Token::BinaryOperator(BinaryOperator::Additive(_))
+        ) {
+            return self.parse_postfix_expression();
+        }
+        let Spanned {
+            node: operator,
+            span,
+        } = self.pop_front();
+        // The smallest int has no positive counterpart to negate, so it is folded directly.
+        if operator.to_string() == "-"
+            && matches!(self.at(), Token::Number(value) if value.parse::<i32>().is_err())
+        {
+            let number = self.pop_front();
+            return Ok(Spanned::new(
+                Expression::NumericLiteral(i32::MIN),
+                span.to(number.span),
+            ));
+        }
+        let operand = self.nested(Self::parse_unary_expression)?;
+        let span = span.to(operand.span);
+        Ok(Spanned::new(
+            Expression::UnaryExpression {
+                operand: Box::new(operand),
+                operator: operator.to_string(),
             },
-            t => panic!("syntax error: Not a valid constant assignment (expecting '=' or ';', but '{}' found)", t.to_string())
+            span,
+        ))
+    }
+    /// Any number of calls, indexings and field accesses following a primary expression, as in
+    /// `f(a)[0].b`.
+    fn parse_postfix_expression(&mut self) -> ParseResult<Spanned<Expression>> {
+        let mut expression = self.parse_primary_expression()?;
+        loop {
+            expression = match self.at() {
+                Token::OpenParen => {
+                    self.pop_front();
+                    let arguments = self.parse_list(Token::CloseParen, Self::parse_expression)?;
+                    let close = self.expect(Token::CloseParen, "',' or ')'")?;
+                    let span = expression.span.to(close);
+                    Spanned::new(
+                        Expression::Call {
+                            callee: Box::new(expression),
+                            arguments,
+                        },
+                        span,
+                    )
+                }
+                Token::OpenBracket => {
+                    self.pop_front();
+                    let index = self.parse_expression()?;
+                    let close = self.expect(Token::CloseBracket, "']'")?;
+                    let span = expression.span.to(close);
+                    Spanned::new(
+                        Expression::Index {
+                            target: Box::new(expression),
+                            index: Box::new(index),
+                        },
+                        span,
+                    )
+                }
+                Token::Dot => {
+                    self.pop_front();
+                    let field = self.expect_identifier("a field name after '.'")?;
+                    let span = expression.span.to(field.span);
+                    Spanned::new(
+                        Expression::Member {
+                            target: Box::new(expression),
+                            field: field.node,
+                        },
+                        span,
+                    )
+                }
+                _ => return Ok(expression),
+            }
         }
     }
-    fn parse_const(&mut self) -> Statement {
-        assert!(self.pop_front() == Token::Const);
-        let identifier = match self.pop_front() {
-            Token::Identifier(i) => i,
-            _ => panic!("syntax error: let statement should be followed by an identifier."),
+    /// `key: value` in a map literal.
+    fn parse_map_entry(&mut self) -> ParseResult<(String, Spanned<Expression>)> {
+        let key = match self.at() {
+            Token::Identifier(key) | Token::String(key) => key.clone(),
+            _ => return Err(self.unexpected("a key")),
         };
-        match self.pop_front() {
-            Token::Semicolon => {
-                panic!("constant declaration should contain value")
+        self.pop_front();
+        self.expect(Token::Colon, "':'")?;
+        Ok((key, self.parse_expression()?))
+    }
+    /// Whether the `{` at the start of a statement opens a map literal rather than a block.
+    /// It does when followed by `key:`, which can't start a statement; `{}` is an empty block.
+    fn at_map_literal(&self) -> bool {
+        matches!(
+            self.tokens.get(1).map(|token| &token.node),
+            Some(Token::Identifier(_) | Token::String(_))
+        ) && matches!(
+            self.tokens.get(2).map(|token| &token.node),
+            Some(Token::Colon)
+        )
+    }
+    /// Parses comma-separated items up to `end`, which is left for the caller to consume.
+    /// A trailing comma is accepted.
+    fn parse_list<T>(
+        &mut self,
+        end: Token,
+        parse_item: fn(&mut Self) -> ParseResult<T>,
+    ) -> ParseResult<Vec<T>> {
+        let mut items = Vec::new();
+        while *self.at() != end {
+            items.push(parse_item(self)?);
+            if *self.at() != Token::Comma {
+                break;
             }
-            Token::Equals => {
-                let value = self.parse_expression();
-                if self.pop_front() != Token::Semicolon {
-                    panic!("syntax error: Variable declaration statement must end with semicolon.")
-                }
-                Statement::ConstDeclaration { identifier, value }
+            self.pop_front();
+        }
+        Ok(items)
+    }
+    fn parse_primary_expression(&mut self) -> ParseResult<Spanned<Expression>> {
+        if !matches!(
+            self.at(),
+            Token::Identifier(_)
+                | Token::Number(_)
+                | Token::Float(_)
+                | Token::String(_)
+                | Token::True
+                | Token::False
+                | Token::Null
+                | Token::OpenParen
+                | Token::OpenBracket
+                | Token::OpenBrace
+                | Token::Fn
+        ) {
+            return Err(self.unexpected("an expression"));
+        }
+        let Spanned { node: token, span } = self.pop_front();
+        Ok(match token {
+            Token::Identifier(parameter) if *self.at() == Token::Arrow => {
+                self.parse_arrow_function(parameter, span)?
             }
-            t => panic!(
-                "syntax error: Not a valid let assignment (expecting '=' or ';', but '{}' found)",
-                t.to_string()
+            Token::Identifier(value) => Spanned::new(Expression::Identifier(value), span),
+            // The lexer only produces number tokens that fit an i32 or a finite f64, apart from
+            // `2147483648`, which is only valid after a unary `-`.
+            Token::Number(value) => match value.parse::<i32>() {
+                Ok(number) => Spanned::new(Expression::NumericLiteral(number), span),
+                Err(_) => return Err(Spanned::new(ParseError::InvalidNumber(value), span)),
+            },
+            Token::Float(value) => Spanned::new(
+                Expression::FloatLiteral(value.parse::<f64>().unwrap()),
+                span,
             ),
+            Token::String(value) => Spanned::new(Expression::StringLiteral(value), span),
+            Token::True => Spanned::new(Expression::BoolLiteral(true), span),
+            Token::False => Spanned::new(Expression::BoolLiteral(false), span),
+            Token::Null => Spanned::new(Expression::NullLiteral, span),
+            Token::OpenParen => {
+                let expr = self.parse_expression()?;
+                let close = self.expect(Token::CloseParen, "')'")?;
+                Spanned::new(expr.node, span.to(close))
+            }
+            Token::OpenBracket => {
+                let elements = self.parse_list(Token::CloseBracket, Self::parse_expression)?;
+                let close = self.expect(Token::CloseBracket, "',' or ']'")?;
+                Spanned::new(Expression::ArrayLiteral(elements), span.to(close))
+            }
+            // In an expression, `{` can only be a map literal.
+            Token::OpenBrace => {
+                let entries = self.parse_list(Token::CloseBrace, Self::parse_map_entry)?;
+                let close = self.expect(Token::CloseBrace, "',' or '}'")?;
+                Spanned::new(Expression::MapLiteral(entries), span.to(close))
+            }
+            Token::Fn => {
+                let function = self.parse_function(None, span)?;
+                Spanned::new(Expression::Function(function.node), function.span)
+            }
+            _ => unreachable!(),
+        })
+    }
+    fn parse_let(&mut self) -> ParseResult<Spanned<Statement>> {
+        let start = self.expect(Token::Let, "'let'")?;
+        let identifier = self.expect_identifier("an identifier after 'let'")?;
+        let value = match self.at() {
+            Token::Equals => {
+                self.pop_front();
+                Some(self.parse_expression()?)
+            }
+            Token::Semicolon | Token::Eof | Token::CloseBrace => None,
+            _ => return Err(self.unexpected("'=' or ';'")),
+        };
+        let end = value.as_ref().map_or(identifier.span, |value| value.span);
+        let span = self.expect_statement_end(start.to(end))?;
+        Ok(Spanned::new(
+            Statement::VarDeclaration {
+                identifier: identifier.node,
+                value,
+            },
+            span,
+        ))
+    }
+    fn parse_const(&mut self) -> ParseResult<Spanned<Statement>> {
+        let start = self.expect(Token::Const, "'const'")?;
+        let identifier = self.expect_identifier("an identifier after 'const'")?;
+        match self.at() {
+            Token::Equals => {
+                self.pop_front();
+            }
+            Token::Semicolon => {
+                return Err(Spanned::new(
+                    ParseError::MissingConstInitializer(identifier.node),
+                    start.to(identifier.span),
+                ))
+            }
+            _ => return Err(self.unexpected("'='")),
         }
+        let value = self.parse_expression()?;
+        let span = self.expect_statement_end(start.to(value.span))?;
+        Ok(Spanned::new(
+            Statement::ConstDeclaration {
+                identifier: identifier.node,
+                value,
+            },
+            span,
+        ))
     }
-    fn parse_identifier(&mut self) -> Statement {
-        let left = self.parse_expression();
+    fn parse_identifier(&mut self) -> ParseResult<Spanned<Statement>> {
+        let left = self.parse_expression()?;
         if *self.at() != Token::Equals {
-            self.pop_front();
-            return Statement::Expression(left);
+            let span = self.expect_statement_end(left.span)?;
+            return Ok(Spanned::new(Statement::Expression(left), span));
         }
+        let start = left.span;
+        let target = match left.node {
+            Expression::Identifier(identifier) => AssignmentTarget::Variable(identifier),
+            Expression::Index { target, index } => AssignmentTarget::Index {
+                target: *target,
+                index: *index,
+            },
+            Expression::Member { target, field } => AssignmentTarget::Member {
+                target: *target,
+                field,
+            },
+            _ => return Err(Spanned::new(ParseError::InvalidAssignmentTarget, left.span)),
+        };
         self.pop_front();
-        let value = self.parse_expression();
-        if *self.at() == Token::Semicolon {
-            self.pop_front();
-        }
-        assert!(matches!(left, Expression::Identifier(_)));
-        if let Expression::Identifier(identifier) = left {
-            Statement::VarAssignment {
-                identifier,
-                value
+        let value = self.parse_expression()?;
+        let span = self.expect_statement_end(start.to(value.span))?;
+        Ok(Spanned::new(
+            Statement::VarAssignment { target, value },
+            span,
+        ))
+    }
+    /// Precedence, from loosest to tightest:
+    /// `||`, `&&`, `==` `!=`, `<` `<=` `>` `>=`, `..` `..=`, `+` `-`, `*` `/` `%`, the unary
+    /// `-` `+` `!`, then calls, indexing and field access.
+    /// The body of a short function `x => ...` takes the rest of the expression.
+    fn parse_expression(&mut self) -> ParseResult<Spanned<Expression>> {
+        self.nested(Self::parse_logical_or_expression)
+    }
+    fn parse_block(&mut self) -> ParseResult<Spanned<Statement>> {
+        let start = self.expect(Token::OpenBrace, "'{'")?;
+        let body = self.parse_statements();
+        let end = self.expect(Token::CloseBrace, "'}'")?;
+        Ok(Spanned::new(Statement::Block(body), start.to(end)))
+    }
+    /// `if condition { ... }`, optionally followed by `else if ...` or `else { ... }`.
+    /// The condition is any expression, so `if (a < b)` and `if a < b` are both accepted.
+    fn parse_if(&mut self) -> ParseResult<Spanned<Statement>> {
+        let start = self.expect(Token::If, "'if'")?;
+        let condition = self.parse_expression()?;
+        let then_branch = self.parse_block()?;
+        let mut end = then_branch.span;
+        let else_branch = match self.at() {
+            Token::Else => {
+                self.pop_front();
+                let else_branch = match self.at() {
+                    Token::If => self.nested(Self::parse_if)?,
+                    _ => self.parse_block()?,
+                };
+                end = else_branch.span;
+                Some(Box::new(else_branch))
             }
-        }
+            _ => None,
+        };
+        Ok(Spanned::new(
+            Statement::If {
+                condition,
+                then_branch: Box::new(then_branch),
+                else_branch,
+            },
+            start.to(end),
+        ))
+    }
+    fn parse_while(&mut self) -> ParseResult<Spanned<Statement>> {
+        let start = self.expect(Token::While, "'while'")?;
+        let condition = self.parse_expression()?;
+        self.loop_depth += 1;
+        let body = self.parse_block();
+        self.loop_depth -= 1;
+        let body = body?;
+        let span = start.to(body.span);
+        Ok(Spanned::new(
+            Statement::While {
+                condition,
+                body: Box::new(body),
+            },
+            span,
+        ))
+    }
+    /// `for variable in iterable { ... }`.
+    fn parse_for(&mut self) -> ParseResult<Spanned<Statement>> {
+        let start = self.expect(Token::For, "'for'")?;
+        let variable = self.expect_identifier("a loop variable after 'for'")?.node;
+        self.expect(Token::In, "'in'")?;
+        let iterable = self.parse_expression()?;
+        self.loop_depth += 1;
+        let body = self.parse_block();
+        self.loop_depth -= 1;
+        let body = body?;
+        let span = start.to(body.span);
+        Ok(Spanned::new(
+            Statement::For {
+                variable,
+                iterable,
+                body: Box::new(body),
+            },
+            span,
+        ))
+    }
+    /// `fn name(parameters) { ... }`.
+    fn parse_function_declaration(&mut self) -> ParseResult<Spanned<Statement>> {
+        let start = self.expect(Token::Fn, "'fn'")?;
+        let name = self.expect_identifier("a function name after 'fn'")?.node;
+        let function = self.parse_function(Some(name), start)?;
+        Ok(Spanned::new(
+            Statement::FunctionDeclaration(function.node),
+            function.span,
+        ))
+    }
+    /// The parameters and body of a function, `start` being the span of `fn`.
+    ///
+    /// Loops around the function don't extend into its body, so `break` can't leave it.
+    /// As in Rust, a final expression statement without `;` is the value returned, so
+    /// `fn(x) { x + 1 }` returns `x + 1`.
+    fn parse_function(
+        &mut self,
+        name: Option<String>,
+        start: Span,
+    ) -> ParseResult<Spanned<Rc<FunctionDefinition>>> {
+        self.expect(Token::OpenParen, "'('")?;
+        let parameters = self.parse_list(Token::CloseParen, |parser| {
+            Ok(parser.expect_identifier("a parameter name")?.node)
+        })?;
+        self.expect(Token::CloseParen, "',' or ')'")?;
+        let loop_depth = std::mem::replace(&mut self.loop_depth, 0);
+        self.function_depth += 1;
+        let body = self.parse_block();
+        self.function_depth -= 1;
+        self.loop_depth = loop_depth;
+        let Spanned {
+            node: Statement::Block(mut body),
+            span: end,
+        } = body?
         else {
-            panic!()
+            unreachable!()
+        };
+        // The span of an expression statement only differs from its expression's when it
+        // includes the `;`.
+        let trailing_value = body.pop_if(|statement| {
+            matches!(&statement.node, Statement::Expression(value) if value.span == statement.span)
+        });
+        if let Some(Spanned {
+            node: Statement::Expression(value),
+            span,
+        }) = trailing_value
+        {
+            body.push(Spanned::new(Statement::Return(Some(value)), span));
         }
-        
+        Ok(Spanned::new(
+            Rc::new(FunctionDefinition {
+                name,
+                parameters,
+                body,
+            }),
+            start.to(end),
+        ))
     }
-    fn parse_expression(&mut self) -> Expression {
-        self.parse_additive_expression()
+    /// `parameter => value`, the identifier being already consumed.
+    /// The body extends as far as an expression can, so `x => x + 1` returns `x + 1`.
+    fn parse_arrow_function(
+        &mut self,
+        parameter: String,
+        start: Span,
+    ) -> ParseResult<Spanned<Expression>> {
+        self.expect(Token::Arrow, "'=>'")?;
+        let value = self.parse_expression()?;
+        let span = start.to(value.span);
+        let body = vec![Spanned::new(Statement::Return(Some(value)), span)];
+        Ok(Spanned::new(
+            Expression::Function(Rc::new(FunctionDefinition {
+                name: None,
+                parameters: vec![parameter],
+                body,
+            })),
+            span,
+        ))
     }
-    fn parse_statement(&mut self) -> Statement {
+    /// `return` with an optional value. Like `break`, it is recorded as an error outside of a
+    /// function but parsed as usual.
+    fn parse_return(&mut self) -> ParseResult<Spanned<Statement>> {
+        let start = self.expect(Token::Return, "'return'")?;
+        if self.function_depth == 0 {
+            self.errors
+                .push(Spanned::new(ParseError::OutsideFunction, start));
+        }
+        let value = match self.at() {
+            Token::Semicolon | Token::CloseBrace | Token::Eof => None,
+            _ => Some(self.parse_expression()?),
+        };
+        let end = value.as_ref().map_or(start, |value| value.span);
+        let span = self.expect_statement_end(start.to(end))?;
+        Ok(Spanned::new(Statement::Return(value), span))
+    }
+    /// `break` or `continue`. Outside of a loop the error is recorded but parsing goes on as
+    /// usual, since the statement itself is well-formed.
+    fn parse_loop_control(&mut self) -> ParseResult<Spanned<Statement>> {
+        let Spanned {
+            node: keyword,
+            span,
+        } = self.pop_front();
+        if self.loop_depth == 0 {
+            self.errors
+                .push(Spanned::new(ParseError::OutsideLoop(keyword.clone()), span));
+        }
+        let statement = match keyword {
+            Token::Break => Statement::Break,
+            _ => Statement::Continue,
+        };
+        let span = self.expect_statement_end(span)?;
+        Ok(Spanned::new(statement, span))
+    }
+    fn parse_statement(&mut self) -> ParseResult<Spanned<Statement>> {
+        self.nested(Self::parse_unnested_statement)
+    }
+    fn parse_unnested_statement(&mut self) -> ParseResult<Spanned<Statement>> {
         match *self.at() {
+            Token::While => self.parse_while(),
+            Token::For => self.parse_for(),
+            // `fn(...)` without a name is an anonymous function used as an expression.
+            Token::Fn if matches!(self.tokens[1].node, Token::Identifier(_)) => {
+                self.parse_function_declaration()
+            }
+            Token::Return => self.parse_return(),
+            Token::Break | Token::Continue => self.parse_loop_control(),
             Token::Let => self.parse_let(),
             Token::Const => self.parse_const(),
-            Token::Identifier(_) => self.parse_identifier(),
-            _ => Statement::Expression(self.parse_expression()),
+            Token::If => self.parse_if(),
+            Token::OpenBrace if !self.at_map_literal() => self.parse_block(),
+            _ => self.parse_identifier(),
+        }
+    }
+    /// Parses statements up to the end of the input or of the enclosing block.
+    ///
+    /// After a syntax error the parser records it, skips ahead to the next statement and carries
+    /// on, so that every error can be reported together.
+    fn parse_statements(&mut self) -> Vec<Spanned<Statement>> {
+        let mut body = Vec::new();
+        while !matches!(self.at(), Token::Eof | Token::CloseBrace) {
+            // Empty statements, as in `{ ... };`.
+            if *self.at() == Token::Semicolon {
+                self.pop_front();
+                continue;
+            }
+            let remaining = self.tokens.len();
+            match self.parse_statement() {
+                Ok(statement) => body.push(statement),
+                Err(error) => {
+                    self.errors.push(error);
+                    // Make sure the offending token is skipped even when nothing was consumed.
+                    if self.tokens.len() == remaining {
+                        self.pop_front();
+                    }
+                    self.synchronize();
+                }
+            }
         }
+        body
     }
-    pub fn produce_ast(&mut self) -> Program {
+    /// Parses the whole token stream, returning every syntax error found if there is any.
+    pub fn produce_ast(&mut self) -> Result<Program, Vec<Spanned<ParseError>>> {
         let mut program = Program::new();
-
-        while !self.eof() {
-            let s = self.parse_statement();
-            program.body.push(s);
+        loop {
+            program.body.append(&mut self.parse_statements());
+            if self.eof() {
+                break;
+            }
+            // A `}` without a matching `{`.
+            self.errors.push(self.unexpected("a statement"));
+            self.pop_front();
+        }
+        if !self.errors.is_empty() {
+            return Err(std::mem::take(&mut self.errors));
         }
-        program
+        Ok(program)
     }
 }
@@ -0,0 +1,56 @@
+use std::fmt::Display;
+
+/// A region of the source code.
+///
+/// `start` and `end` are byte offsets (`end` is exclusive), so `&source[span.start..span.end]`
+/// gives back the exact text. `line` and `column` describe where the region starts and are both
+/// 1-based, counting columns in characters.
+/// Source lexed from an offset, as the shell does to tell its inputs apart, has its byte offsets
+/// shifted by it, while lines and columns still count from its start.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize, line: usize, column: usize) -> Self {
+        Self {
+            start,
+            end,
+            line,
+            column,
+        }
+    }
+    /// The smallest span covering both `self` and `other`.
+    /// `self` is expected to come first in the source.
+    pub fn to(self, other: Span) -> Self {
+        Self {
+            start: self.start,
+            end: self.end.max(other.end),
+            line: self.line,
+            column: self.column,
+        }
+    }
+}
+
+impl Display for Span {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.line, self.column)
+    }
+}
+
+/// A value paired with the span of source it came from.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Spanned<T> {
+    pub node: T,
+    pub span: Span,
+}
+
+impl<T> Spanned<T> {
+    pub fn new(node: T, span: Span) -> Self {
+        Self { node, span }
+    }
+}
@@ -1,63 +1,214 @@
-use std::{env, fs, io::Write};
+use std::{env, fs, panic, process::ExitCode, thread};
 
-use crate::runtime::{environment::Environment, values::RuntimeValue};
+use crate::{
+    frontend::{ast::Program, lexer::tokenize, parser::Parser},
+    runtime::environment::{Environment, OverflowMode},
+};
 
+mod diagnostics;
 mod frontend;
+mod repl;
 mod runtime;
 #[cfg(test)]
 mod tests;
 
-fn read_string() -> String {
-    let mut input = String::new();
-    std::io::stdin()
-        .read_line(&mut input)
-        .expect("Cannot read user input");
-    input
-}
-
-fn shell() {
-    println!("Custom lang shell, v0.0.0");
-    let mut environment = Environment::create(None);
-    environment
-        .declare_variable("test_variable", RuntimeValue::NumberValue(3))
-        .unwrap()
-        .declare_variable("true", RuntimeValue::Bool(true))
-        .unwrap()
-        .declare_variable("false", RuntimeValue::Bool(false))
-        .unwrap()
-        .declare_variable("null", RuntimeValue::NullValue)
-        .unwrap();
-    loop {
-        print!("> ");
-        std::io::stdout().flush().expect("io flush err");
-        let input = read_string();
-        if input == "exit\n".to_string() {
-            println!("Shell exits.");
-            break;
-        }
-        let program = frontend::parser::Parser::initialize(input).produce_ast();
-        let result = environment.evaluate_program(program);
-        // println!("{:#?}", result);
+const USAGE: &str = "\
+Usage: awa1 [command] [options]
+
+Commands:
+  run <file>       Run a script; `awa1 <file>` does the same
+  repl             Start the interactive shell (the default)
+  tokens <file>    Print the tokens of a script
+  ast <file>       Print the syntax tree of a script
+  check <file>     Report the syntax errors of a script without running it
+  explain [code]   Describe an error code, or list them all
+
+Options:
+  --strict            Require conditions to be bools instead of testing their truthiness
+  --overflow=<mode>   What int arithmetic does on overflow: checked (the default), wrapping
+                      or saturating
+  -h, --help          Print this help
+
+Exit status:
+  0 on success, 1 on a runtime error, 64 on a usage error, 65 on a syntax error, and 66 when
+  the file can't be read.
+";
+
+// Exit statuses, following `sysexits.h` where it has one.
+const EXIT_RUNTIME_ERROR: u8 = 1;
+const EXIT_USAGE: u8 = 64;
+const EXIT_SYNTAX_ERROR: u8 = 65;
+const EXIT_NO_INPUT: u8 = 66;
+
+/// The stack size of the thread running the interpreter, which recurses for every nested call
+/// and expression. It leaves room for `MAX_CALL_DEPTH` calls even with the large stack frames
+/// of debug builds; only the part actually used is allocated.
+const STACK_SIZE: usize = 256 * 1024 * 1024;
+
+/// Command line options, which may appear anywhere among the arguments.
+#[derive(Debug, Default)]
+struct Options {
+    help: bool,
+    strict: bool,
+    overflow_mode: OverflowMode,
+}
+
+impl Options {
+    /// Splits `args` into options and the other arguments.
+    fn parse(args: &[String]) -> Result<(Self, Vec<&str>), String> {
+        let mut options = Self::default();
+        let mut arguments = Vec::new();
+        for arg in args {
+            match arg.as_str() {
+                "-h" | "--help" => options.help = true,
+                "--strict" => options.strict = true,
+                arg => match arg.strip_prefix("--overflow=") {
+                    Some("checked") => options.overflow_mode = OverflowMode::Checked,
+                    Some("wrapping") => options.overflow_mode = OverflowMode::Wrapping,
+                    Some("saturating") => options.overflow_mode = OverflowMode::Saturating,
+                    Some(mode) => {
+                        return Err(format!(
+                        "Unknown overflow mode: {mode}, expected checked, wrapping or saturating"
+                    ))
+                    }
+                    None if arg.starts_with('-') => return Err(format!("Unknown option: {arg}")),
+                    None => arguments.push(arg),
+                },
+            }
+        }
+        Ok((options, arguments))
+    }
+    /// A fresh global environment configured by the options.
+    fn environment(&self) -> Environment {
+        let mut environment = Environment::create(None);
+        environment
+            .set_strict_conditions(self.strict)
+            .set_overflow_mode(self.overflow_mode);
+        environment
     }
 }
 
-fn main() {
-    let args: Vec<String> = env::args().collect();
-    if let Some(arg1) = args.get(1) {
-        if arg1 != "shell" {
-            return;
+fn explain(code: Option<&str>) -> ExitCode {
+    let Some(code) = code else {
+        println!("Usage: explain <code>\n\nKnown error codes:");
+        for error_code in diagnostics::ERROR_CODES {
+            println!("  {}  {}", error_code.code, error_code.summary);
+        }
+        return ExitCode::SUCCESS;
+    };
+    match diagnostics::explain(code) {
+        Some(error_code) => {
+            println!(
+                "{}: {}\n\n{}",
+                error_code.code, error_code.summary, error_code.explanation
+            );
+            ExitCode::SUCCESS
+        }
+        None => {
+            eprintln!("Unknown error code: {code}");
+            ExitCode::from(EXIT_USAGE)
         }
-        shell();
-        return;
     }
+}
 
-    let file_path = &args[1];
-    println!("In file '{}'", file_path);
+fn read_source(file_path: &str) -> Result<String, ExitCode> {
+    fs::read_to_string(file_path).map_err(|error| {
+        eprintln!("Cannot read {file_path}: {error}");
+        ExitCode::from(EXIT_NO_INPUT)
+    })
+}
 
-    let content = fs::read_to_string(file_path).expect("Should have been able to read the file");
+/// Lexes and parses `source`, reporting every error found.
+fn parse(source: &str, file_name: &str) -> Result<Program, ExitCode> {
+    let mut parser = Parser::initialize(source.to_string()).map_err(|errors| {
+        diagnostics::emit(&errors, file_name, source);
+        ExitCode::from(EXIT_SYNTAX_ERROR)
+    })?;
+    parser.produce_ast().map_err(|errors| {
+        diagnostics::emit(&errors, file_name, source);
+        ExitCode::from(EXIT_SYNTAX_ERROR)
+    })
+}
 
-    println!("<FILE CONTENT> \n{content}");
+fn run(file_path: &str, options: &Options) -> Result<(), ExitCode> {
+    let source = read_source(file_path)?;
+    let program = parse(&source, file_path)?;
+    if let Err(error) = options.environment().evaluate_program(program) {
+        diagnostics::emit([&error], file_path, &source);
+        return Err(ExitCode::from(EXIT_RUNTIME_ERROR));
+    }
+    Ok(())
+}
+
+/// Prints each token with its position.
+fn print_tokens(file_path: &str) -> Result<(), ExitCode> {
+    let source = read_source(file_path)?;
+    let tokens = tokenize(source.clone()).map_err(|errors| {
+        diagnostics::emit(&errors, file_path, &source);
+        ExitCode::from(EXIT_SYNTAX_ERROR)
+    })?;
+    for token in tokens {
+        println!("{}\t{:?}", token.span, token.node);
+    }
+    Ok(())
+}
+
+fn print_ast(file_path: &str) -> Result<(), ExitCode> {
+    let source = read_source(file_path)?;
+    println!("{:#?}", parse(&source, file_path)?);
+    Ok(())
+}
+
+fn check(file_path: &str) -> Result<(), ExitCode> {
+    let source = read_source(file_path)?;
+    parse(&source, file_path)?;
+    println!("{file_path}: no errors found");
+    Ok(())
+}
 
-    let parse_result = frontend::parser::Parser::initialize(content).produce_ast();
-    println!("parse result: {:?}", parse_result);
+fn main() -> ExitCode {
+    thread::Builder::new()
+        .stack_size(STACK_SIZE)
+        .spawn(run_command_line)
+        .expect("Cannot start the interpreter thread")
+        .join()
+        .unwrap_or_else(|payload| panic::resume_unwind(payload))
+}
+
+fn run_command_line() -> ExitCode {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let (options, arguments) = match Options::parse(&args) {
+        Ok(parsed) => parsed,
+        Err(message) => {
+            eprintln!("{message}\nRun `awa1 --help` for usage.");
+            return ExitCode::from(EXIT_USAGE);
+        }
+    };
+    if options.help {
+        print!("{USAGE}");
+        return ExitCode::SUCCESS;
+    }
+    let result = match arguments[..] {
+        [] | ["repl"] | ["shell"] => {
+            repl::shell(|| options.environment());
+            Ok(())
+        }
+        ["explain"] => return explain(None),
+        ["explain", code] => return explain(Some(code)),
+        ["run", file_path] => run(file_path, &options),
+        ["tokens", file_path] => print_tokens(file_path),
+        ["ast", file_path] => print_ast(file_path),
+        ["check", file_path] => check(file_path),
+        [file_path] if !matches!(file_path, "run" | "tokens" | "ast" | "check") => {
+            run(file_path, &options)
+        }
+        _ => {
+            eprintln!(
+                "Unexpected arguments: {}\nRun `awa1 --help` for usage.",
+                arguments.join(" ")
+            );
+            Err(ExitCode::from(EXIT_USAGE))
+        }
+    };
+    result.err().unwrap_or(ExitCode::SUCCESS)
 }
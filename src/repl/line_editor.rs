@@ -0,0 +1,519 @@
+use std::{
+    fs::{self, OpenOptions},
+    io::{self, BufRead, IsTerminal, Read, Write},
+    path::PathBuf,
+    process::{Command, Stdio},
+};
+
+/// How many lines of history are kept, in memory and in the history file.
+const MAX_HISTORY: usize = 1000;
+
+/// What reading a line gave.
+#[derive(Debug, PartialEq)]
+pub enum ReadLine {
+    /// A line, without its line break.
+    Line(String),
+    /// Ctrl-C: the line was abandoned.
+    Interrupted,
+    /// Ctrl-D on an empty line, or the end of the input.
+    Eof,
+}
+
+/// A key, as decoded from the bytes a terminal sends.
+#[derive(Debug, PartialEq)]
+pub enum Key {
+    Char(char),
+    /// A control character, as the lowercase letter typed with Ctrl.
+    Ctrl(char),
+    Enter,
+    Tab,
+    Backspace,
+    Delete,
+    Left,
+    Right,
+    Up,
+    Down,
+    Home,
+    End,
+    /// An escape sequence or a byte that means nothing here.
+    Unknown,
+}
+
+/// Reads lines from stdin, with editing, history and completion when stdin is a terminal.
+pub struct LineEditor {
+    history: Vec<String>,
+    /// Where the history is kept between sessions, when there is a place for it.
+    history_path: Option<PathBuf>,
+    interactive: bool,
+}
+
+impl LineEditor {
+    /// An editor whose history is loaded from and saved to `history_path`.
+    pub fn new(history_path: Option<PathBuf>) -> Self {
+        let interactive = io::stdin().is_terminal() && io::stdout().is_terminal();
+        let mut history: Vec<String> = history_path
+            .as_ref()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .map(|content| {
+                content
+                    .lines()
+                    .filter(|line| !line.trim().is_empty())
+                    .map(str::to_string)
+                    .collect()
+            })
+            .unwrap_or_default();
+        if history.len() > MAX_HISTORY {
+            history.drain(..history.len() - MAX_HISTORY);
+            if let Some(path) = &history_path {
+                // Losing the history is no reason to stop the shell.
+                let _ = fs::write(path, history.join("\n") + "\n");
+            }
+        }
+        Self {
+            history,
+            history_path,
+            interactive,
+        }
+    }
+
+    /// Reads a line after printing `prompt`.
+    /// Tab completes the word before the cursor with what `complete` gives for it.
+    pub fn read_line(
+        &mut self,
+        prompt: &str,
+        complete: impl Fn(&str) -> Vec<String>,
+    ) -> io::Result<ReadLine> {
+        if !self.interactive {
+            return read_plain(prompt);
+        }
+        match RawMode::enable() {
+            Ok(_raw_mode) => self.edit(prompt, complete),
+            // Without `stty`, there is no editing, but the shell still works.
+            Err(_) => {
+                self.interactive = false;
+                read_plain(prompt)
+            }
+        }
+    }
+
+    fn edit(
+        &mut self,
+        prompt: &str,
+        complete: impl Fn(&str) -> Vec<String>,
+    ) -> io::Result<ReadLine> {
+        let mut input = io::stdin().lock();
+        let mut output = io::stdout().lock();
+        let mut buffer = Buffer::default();
+        // `history.len()` stands for the line being typed, kept in `draft` while browsing.
+        let mut history_index = self.history.len();
+        let mut draft = Buffer::default();
+        // A key that ended a reverse search, and has yet to be handled.
+        let mut pending = None;
+        redraw(&mut output, prompt, &buffer)?;
+        loop {
+            let key = match pending.take() {
+                Some(key) => key,
+                None => match read_key(&mut input)? {
+                    Some(key) => key,
+                    None => {
+                        write!(output, "\r\n")?;
+                        return Ok(ReadLine::Eof);
+                    }
+                },
+            };
+            match key {
+                Key::Enter => {
+                    write!(output, "\r\n")?;
+                    let line = buffer.text();
+                    self.add_history(&line);
+                    return Ok(ReadLine::Line(line));
+                }
+                Key::Ctrl('c') => {
+                    write!(output, "^C\r\n")?;
+                    return Ok(ReadLine::Interrupted);
+                }
+                Key::Ctrl('d') if buffer.is_empty() => {
+                    write!(output, "\r\n")?;
+                    return Ok(ReadLine::Eof);
+                }
+                Key::Ctrl('d') | Key::Delete => buffer.delete(),
+                Key::Backspace => buffer.backspace(),
+                Key::Ctrl('a') | Key::Home => buffer.move_home(),
+                Key::Ctrl('e') | Key::End => buffer.move_end(),
+                Key::Left => buffer.move_left(),
+                Key::Right => buffer.move_right(),
+                Key::Ctrl('w') => buffer.delete_word(),
+                Key::Up if history_index > 0 => {
+                    if history_index == self.history.len() {
+                        draft = buffer;
+                    }
+                    history_index -= 1;
+                    buffer = Buffer::new(&self.history[history_index]);
+                }
+                Key::Down if history_index < self.history.len() => {
+                    history_index += 1;
+                    buffer = match self.history.get(history_index) {
+                        Some(line) => Buffer::new(line),
+                        None => draft.clone(),
+                    };
+                }
+                Key::Ctrl('r') => {
+                    pending = self.reverse_search(&mut input, &mut output, &mut buffer)?;
+                }
+                Key::Tab => complete_word(&mut output, &mut buffer, &complete)?,
+                Key::Char(character) => buffer.insert(&character.to_string()),
+                _ => {}
+            }
+            redraw(&mut output, prompt, &buffer)?;
+        }
+    }
+
+    /// Searches the history backwards for lines containing what is typed, Ctrl-R going to the
+    /// next older match. Any other key puts the match in `buffer` and is returned to be handled
+    /// as usual, while Ctrl-C or Ctrl-G leave `buffer` as it was.
+    fn reverse_search(
+        &self,
+        input: &mut impl Read,
+        output: &mut impl Write,
+        buffer: &mut Buffer,
+    ) -> io::Result<Option<Key>> {
+        let mut query = String::new();
+        let mut found: Option<usize> = None;
+        loop {
+            let matched = found.map_or("", |index| self.history[index].as_str());
+            write!(output, "\r(reverse-i-search)`{query}': {matched}\x1b[K")?;
+            output.flush()?;
+            let Some(key) = read_key(input)? else {
+                return Ok(None);
+            };
+            match key {
+                Key::Char(character) => {
+                    query.push(character);
+                    // The current match may still contain the longer query.
+                    let before = found.map_or(self.history.len(), |index| index + 1);
+                    found = search_history(&self.history, &query, before);
+                }
+                Key::Backspace => {
+                    query.pop();
+                    found = search_history(&self.history, &query, self.history.len())
+                        .filter(|_| !query.is_empty());
+                }
+                Key::Ctrl('r') => {
+                    let before = found.unwrap_or(self.history.len());
+                    found = search_history(&self.history, &query, before).or(found);
+                }
+                Key::Ctrl('c') | Key::Ctrl('g') => return Ok(None),
+                key => {
+                    if let Some(index) = found {
+                        *buffer = Buffer::new(&self.history[index]);
+                    }
+                    return Ok(Some(key));
+                }
+            }
+        }
+    }
+
+    /// Remembers `line`, unless it is blank or the same as the previous one, and appends it to
+    /// the history file.
+    fn add_history(&mut self, line: &str) {
+        if line.trim().is_empty() || self.history.last().is_some_and(|last| last == line) {
+            return;
+        }
+        self.history.push(line.to_string());
+        if self.history.len() > MAX_HISTORY {
+            self.history.remove(0);
+        }
+        if let Some(path) = &self.history_path {
+            let _ = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .and_then(|mut file| writeln!(file, "{line}"));
+        }
+    }
+}
+
+/// Keeps the terminal in raw mode, where keys are read as they are typed and not echoed, until
+/// dropped.
+struct RawMode {
+    /// The settings to restore, as `stty -g` prints them.
+    saved: String,
+}
+
+impl RawMode {
+    fn enable() -> io::Result<Self> {
+        let saved = stty(&["-g"])?.trim().to_string();
+        stty(&["raw", "-echo"])?;
+        Ok(Self { saved })
+    }
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        let _ = stty(&[&self.saved]);
+    }
+}
+
+/// Runs `stty` on the terminal stdin is, returning what it prints.
+fn stty(arguments: &[&str]) -> io::Result<String> {
+    let output = Command::new("stty")
+        .args(arguments)
+        .stdin(Stdio::inherit())
+        .output()?;
+    if !output.status.success() {
+        let message = String::from_utf8_lossy(&output.stderr).trim().to_string();
+        return Err(io::Error::other(message));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Reads a line without editing, for when stdin is not a terminal.
+fn read_plain(prompt: &str) -> io::Result<ReadLine> {
+    print!("{prompt}");
+    io::stdout().flush()?;
+    let mut line = String::new();
+    if io::stdin().lock().read_line(&mut line)? == 0 {
+        return Ok(ReadLine::Eof);
+    }
+    let line = line.strip_suffix('\n').unwrap_or(&line);
+    Ok(ReadLine::Line(
+        line.strip_suffix('\r').unwrap_or(line).to_string(),
+    ))
+}
+
+/// Completes the word before the cursor when it has a single completion, or as far as all of
+/// them agree, and lists them when that does not get any further.
+fn complete_word(
+    output: &mut impl Write,
+    buffer: &mut Buffer,
+    complete: &impl Fn(&str) -> Vec<String>,
+) -> io::Result<()> {
+    let word = buffer.word_before_cursor();
+    if word.is_empty() {
+        return Ok(());
+    }
+    let mut candidates = complete(&word);
+    candidates.sort();
+    candidates.dedup();
+    if candidates.is_empty() {
+        // The terminal bell.
+        return write!(output, "\x07");
+    }
+    let common = longest_common_prefix(&candidates);
+    if common.len() > word.len() {
+        buffer.insert(&common[word.len()..]);
+    } else if candidates.len() > 1 {
+        write!(output, "\r\n{}\r\n", candidates.join("  "))?;
+    }
+    Ok(())
+}
+
+/// The longest string all of `words` start with.
+pub fn longest_common_prefix(words: &[String]) -> &str {
+    let Some((first, rest)) = words.split_first() else {
+        return "";
+    };
+    let mut prefix = first.as_str();
+    for word in rest {
+        let length = prefix
+            .char_indices()
+            .zip(word.chars())
+            .find(|((_, a), b)| a != b)
+            .map_or(prefix.len().min(word.len()), |((index, _), _)| index);
+        prefix = &prefix[..length];
+    }
+    prefix
+}
+
+/// The index of the most recent line of `history` before `before` that contains `query`.
+pub fn search_history(history: &[String], query: &str, before: usize) -> Option<usize> {
+    history[..before]
+        .iter()
+        .rposition(|line| line.contains(query))
+}
+
+/// Writes the prompt and the line over the current one, and puts the cursor back in place.
+fn redraw(output: &mut impl Write, prompt: &str, buffer: &Buffer) -> io::Result<()> {
+    write!(output, "\r{prompt}{}\x1b[K", buffer.text())?;
+    let after_cursor = buffer.len() - buffer.cursor();
+    if after_cursor > 0 {
+        write!(output, "\x1b[{after_cursor}D")?;
+    }
+    output.flush()
+}
+
+fn read_byte(input: &mut impl Read) -> io::Result<Option<u8>> {
+    let mut byte = [0];
+    match input.read(&mut byte)? {
+        0 => Ok(None),
+        _ => Ok(Some(byte[0])),
+    }
+}
+
+/// Reads the next key from `input`, or `None` at its end.
+pub fn read_key(input: &mut impl Read) -> io::Result<Option<Key>> {
+    let Some(byte) = read_byte(input)? else {
+        return Ok(None);
+    };
+    let key = match byte {
+        b'\r' | b'\n' => Key::Enter,
+        b'\t' => Key::Tab,
+        0x08 | 0x7f => Key::Backspace,
+        0x1b => read_escape_sequence(input)?,
+        0x01..=0x1a => Key::Ctrl((b'a' + byte - 1) as char),
+        0x00..=0x1f => Key::Unknown,
+        0x20..=0x7e => Key::Char(byte as char),
+        _ => read_utf8(byte, input)?,
+    };
+    Ok(Some(key))
+}
+
+/// Reads what follows an escape, like `[A` for the up arrow or `[3~` for Delete.
+fn read_escape_sequence(input: &mut impl Read) -> io::Result<Key> {
+    if !matches!(read_byte(input)?, Some(b'[' | b'O')) {
+        return Ok(Key::Unknown);
+    }
+    let mut parameter = String::new();
+    loop {
+        let Some(byte) = read_byte(input)? else {
+            return Ok(Key::Unknown);
+        };
+        let key = match byte {
+            b'0'..=b'9' | b';' => {
+                parameter.push(byte as char);
+                continue;
+            }
+            b'A' => Key::Up,
+            b'B' => Key::Down,
+            b'C' => Key::Right,
+            b'D' => Key::Left,
+            b'H' => Key::Home,
+            b'F' => Key::End,
+            b'~' => match parameter.as_str() {
+                "1" | "7" => Key::Home,
+                "4" | "8" => Key::End,
+                "3" => Key::Delete,
+                _ => Key::Unknown,
+            },
+            _ => Key::Unknown,
+        };
+        return Ok(key);
+    }
+}
+
+/// Reads the rest of the UTF-8 character starting with `first`.
+fn read_utf8(first: u8, input: &mut impl Read) -> io::Result<Key> {
+    let length = match first {
+        0xc0..=0xdf => 2,
+        0xe0..=0xef => 3,
+        0xf0..=0xf7 => 4,
+        _ => return Ok(Key::Unknown),
+    };
+    let mut bytes = vec![first];
+    for _ in 1..length {
+        match read_byte(input)? {
+            Some(byte) => bytes.push(byte),
+            None => return Ok(Key::Unknown),
+        }
+    }
+    Ok(std::str::from_utf8(&bytes)
+        .ok()
+        .and_then(|text| text.chars().next())
+        .map_or(Key::Unknown, Key::Char))
+}
+
+/// The line being edited, and where the cursor is in it.
+#[derive(Debug, Clone, Default)]
+pub struct Buffer {
+    characters: Vec<char>,
+    /// The index of the character the cursor is on, or the length at the end of the line.
+    cursor: usize,
+}
+
+impl Buffer {
+    /// A buffer holding `text`, with the cursor at its end.
+    pub fn new(text: &str) -> Self {
+        let characters: Vec<char> = text.chars().collect();
+        Self {
+            cursor: characters.len(),
+            characters,
+        }
+    }
+
+    pub fn text(&self) -> String {
+        self.characters.iter().collect()
+    }
+
+    pub fn cursor(&self) -> usize {
+        self.cursor
+    }
+
+    pub fn len(&self) -> usize {
+        self.characters.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.characters.is_empty()
+    }
+
+    /// Inserts `text` at the cursor, and moves the cursor after it.
+    pub fn insert(&mut self, text: &str) {
+        for character in text.chars() {
+            self.characters.insert(self.cursor, character);
+            self.cursor += 1;
+        }
+    }
+
+    /// Deletes the character before the cursor.
+    pub fn backspace(&mut self) {
+        if self.cursor > 0 {
+            self.cursor -= 1;
+            self.characters.remove(self.cursor);
+        }
+    }
+
+    /// Deletes the character under the cursor.
+    pub fn delete(&mut self) {
+        if self.cursor < self.characters.len() {
+            self.characters.remove(self.cursor);
+        }
+    }
+
+    /// Deletes the word before the cursor and the whitespace after it, as Ctrl-W does.
+    pub fn delete_word(&mut self) {
+        let mut start = self.cursor;
+        while start > 0 && self.characters[start - 1].is_whitespace() {
+            start -= 1;
+        }
+        while start > 0 && !self.characters[start - 1].is_whitespace() {
+            start -= 1;
+        }
+        self.characters.drain(start..self.cursor);
+        self.cursor = start;
+    }
+
+    pub fn move_left(&mut self) {
+        self.cursor = self.cursor.saturating_sub(1);
+    }
+
+    pub fn move_right(&mut self) {
+        self.cursor = (self.cursor + 1).min(self.characters.len());
+    }
+
+    pub fn move_home(&mut self) {
+        self.cursor = 0;
+    }
+
+    pub fn move_end(&mut self) {
+        self.cursor = self.characters.len();
+    }
+
+    /// The identifier or keyword the cursor is at the end of, which Tab completes.
+    pub fn word_before_cursor(&self) -> String {
+        let start = self.characters[..self.cursor]
+            .iter()
+            .rposition(|character| !(character.is_alphanumeric() || *character == '_'))
+            .map_or(0, |index| index + 1);
+        self.characters[start..self.cursor].iter().collect()
+    }
+}
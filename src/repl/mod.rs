@@ -0,0 +1,288 @@
+pub mod line_editor;
+pub mod sources;
+
+use std::{env, fs, path::PathBuf};
+
+use self::{
+    line_editor::{LineEditor, ReadLine},
+    sources::Sources,
+};
+use crate::{
+    diagnostics,
+    frontend::{
+        ast::{Program, Statement},
+        lexer::{tokenize, KEYWORDS},
+        parser::Parser,
+        span::Spanned,
+    },
+    runtime::{
+        environment::{Environment, EvalResult},
+        values::RuntimeValue,
+    },
+};
+
+/// How the input of the shell is called in diagnostics.
+const FILE_NAME: &str = "<shell>";
+
+/// The file in the home directory where the lines typed in the shell are kept.
+const HISTORY_FILE_NAME: &str = ".awa1_history";
+
+const COMMANDS: &str = "\
+Commands:
+  :env             List the variables and constants in scope
+  :tokens <code>   Print the tokens of some code
+  :ast <code>      Print the syntax tree of some code
+  :type <expr>     Evaluate an expression, keeping its side effects, and print the type
+                   of its value
+  :load <file>     Run a script in the current session
+  :reset           Start over with a fresh environment
+  :help            Print this help
+  exit             Leave the shell, as Ctrl-D does";
+
+/// Outcome of lexing and parsing the input read so far.
+enum Parsed {
+    Complete(Program),
+    /// The input stops in the middle of a statement, a string or a comment, and more lines could
+    /// complete it.
+    Incomplete,
+    /// The errors have been reported.
+    Invalid,
+}
+
+/// Reads statements from stdin and evaluates them in an environment made by `new_environment`,
+/// until `exit` or the end of the input.
+///
+/// Input that stops in the middle of a statement is continued on the next lines, after a `...`
+/// prompt. A blank line ends it anyway, reporting what is missing, and Ctrl-D discards it.
+/// The value of an input ending with an expression is printed and bound to `_`.
+/// Lines starting with `:` are commands, listed by `:help`.
+///
+/// When stdin is a terminal, lines can be edited, the lines typed before are recalled with the
+/// arrows or Ctrl-R and kept in `~/.awa1_history`, and Tab completes keywords and the names in
+/// scope. Ctrl-C abandons the input.
+pub fn shell(new_environment: impl Fn() -> Environment) {
+    println!("Custom lang shell, v0.0.0");
+    let mut environment = start_session(&new_environment);
+    let mut sources = Sources::default();
+    let mut editor = LineEditor::new(history_path());
+    let mut input = String::new();
+    loop {
+        let prompt = if input.is_empty() { "> " } else { "... " };
+        let line = match editor
+            .read_line(prompt, |word| completions(word, &environment))
+            .expect("Cannot read user input")
+        {
+            ReadLine::Line(line) => line,
+            // Ctrl-C, or Ctrl-D or the end of a piped input in the middle of a statement.
+            ReadLine::Interrupted | ReadLine::Eof if !input.is_empty() => {
+                input.clear();
+                continue;
+            }
+            ReadLine::Interrupted => continue,
+            ReadLine::Eof => {
+                println!("Shell exits.");
+                break;
+            }
+        };
+        if input.is_empty() && line == "exit" {
+            println!("Shell exits.");
+            break;
+        }
+        if let Some(command) = line.trim().strip_prefix(':').filter(|_| input.is_empty()) {
+            run_command(command, &mut environment, &mut sources, &new_environment);
+            continue;
+        }
+        let blank_line = line.trim().is_empty();
+        input.push_str(&line);
+        input.push('\n');
+        let offset = sources.next_offset();
+        let program = match parse(&input, FILE_NAME, offset, !blank_line) {
+            Parsed::Complete(program) => program,
+            Parsed::Incomplete => continue,
+            Parsed::Invalid => {
+                input.clear();
+                continue;
+            }
+        };
+        // Kept, as the functions it declares may fail in a later input.
+        sources.add(FILE_NAME, &input);
+        match evaluate_input(&mut environment, program) {
+            Ok(Some(RuntimeValue::NullValue) | None) => {}
+            Ok(Some(value)) => {
+                println!("{value}");
+                environment
+                    .assign_variable("_", value)
+                    .expect("`_` is declared when the shell starts");
+            }
+            Err(error) => sources.emit([&error]),
+        }
+        input.clear();
+    }
+}
+
+fn history_path() -> Option<PathBuf> {
+    env::var_os("HOME").map(|home| PathBuf::from(home).join(HISTORY_FILE_NAME))
+}
+
+/// The keywords and the names in scope in `environment` that start with `word`.
+pub fn completions(word: &str, environment: &Environment) -> Vec<String> {
+    let names = environment.bindings().into_iter().map(|(name, ..)| name);
+    KEYWORDS
+        .iter()
+        .map(|(keyword, _)| keyword.to_string())
+        .chain(names)
+        .filter(|candidate| candidate.starts_with(word))
+        .collect()
+}
+
+/// A fresh environment for the shell, with `_` declared.
+fn start_session(new_environment: &impl Fn() -> Environment) -> Environment {
+    let mut environment = new_environment();
+    environment
+        .declare_variable("_", RuntimeValue::NullValue)
+        .unwrap();
+    environment
+}
+
+/// Runs `command`, a line of input without its leading `:`.
+fn run_command(
+    command: &str,
+    environment: &mut Environment,
+    sources: &mut Sources,
+    new_environment: &impl Fn() -> Environment,
+) {
+    let (name, argument) = match command.split_once(char::is_whitespace) {
+        Some((name, argument)) => (name, argument.trim()),
+        None => (command, ""),
+    };
+    match name {
+        "env" => {
+            for (name, value, constant) in environment.bindings() {
+                let keyword = if constant { "const" } else { "let" };
+                println!("{keyword} {name} = {value}");
+            }
+        }
+        "tokens" | "ast" | "type" | "load" if argument.is_empty() => {
+            let parameter = if name == "load" { "<file>" } else { "<code>" };
+            println!("Usage: :{name} {parameter}");
+        }
+        "tokens" => match tokenize(argument.to_string()) {
+            Ok(tokens) => {
+                for token in tokens {
+                    println!("{}\t{:?}", token.span, token.node);
+                }
+            }
+            Err(errors) => diagnostics::emit(&errors, FILE_NAME, argument),
+        },
+        "ast" => {
+            if let Parsed::Complete(program) = parse(argument, FILE_NAME, 0, false) {
+                println!("{program:#?}");
+            }
+        }
+        "type" => {
+            let offset = sources.next_offset();
+            let Parsed::Complete(program) = parse(argument, FILE_NAME, offset, false) else {
+                return;
+            };
+            sources.add(FILE_NAME, argument);
+            match evaluate_expression(environment, program) {
+                Some(Ok(value)) => println!("{}", value.type_name()),
+                Some(Err(error)) => sources.emit([&error]),
+                None => println!("{argument} is not an expression"),
+            }
+        }
+        "load" => load(argument, environment, sources),
+        "reset" => {
+            *environment = start_session(new_environment);
+            println!("Started over with a fresh environment.");
+        }
+        "help" => println!("{COMMANDS}"),
+        _ => println!("Unknown command :{name}, see :help for the list of commands."),
+    }
+}
+
+/// Runs the script at `file_path` in `environment`, keeping its declarations.
+fn load(file_path: &str, environment: &mut Environment, sources: &mut Sources) {
+    let source = match fs::read_to_string(file_path) {
+        Ok(source) => source,
+        Err(error) => {
+            eprintln!("Cannot read {file_path}: {error}");
+            return;
+        }
+    };
+    let offset = sources.next_offset();
+    let Parsed::Complete(program) = parse(&source, file_path, offset, false) else {
+        return;
+    };
+    sources.add(file_path, &source);
+    if let Err(error) = environment.evaluate_program(program) {
+        sources.emit([&error]);
+    }
+}
+
+/// Lexes `input` from `offset` and parses it, reporting every error found as coming from
+/// `file_name`. When `can_continue`, errors that more input could fix are not reported, and the
+/// input is deemed incomplete instead.
+fn parse(input: &str, file_name: &str, offset: usize, can_continue: bool) -> Parsed {
+    let mut parser = match Parser::initialize_at(input.to_string(), offset) {
+        Ok(parser) => parser,
+        Err(errors) => {
+            if can_continue && errors.iter().all(|error| error.node.is_incomplete_input()) {
+                return Parsed::Incomplete;
+            }
+            diagnostics::emit_at(&errors, file_name, input, offset);
+            return Parsed::Invalid;
+        }
+    };
+    match parser.produce_ast() {
+        Ok(program) => Parsed::Complete(program),
+        Err(errors)
+            if can_continue && errors.iter().all(|error| error.node.is_incomplete_input()) =>
+        {
+            Parsed::Incomplete
+        }
+        Err(errors) => {
+            diagnostics::emit_at(&errors, file_name, input, offset);
+            Parsed::Invalid
+        }
+    }
+}
+
+/// Evaluates `program` in `environment`, returning the value of its last statement when it is
+/// an expression.
+pub fn evaluate_input(
+    environment: &mut Environment,
+    program: Program,
+) -> EvalResult<Option<RuntimeValue>> {
+    let mut body = program.body;
+    let trailing_expression =
+        body.pop_if(|statement| matches!(statement.node, Statement::Expression(_)));
+    for statement in &body {
+        environment.evaluate(statement)?;
+    }
+    let Some(Spanned {
+        node: Statement::Expression(expression),
+        ..
+    }) = trailing_expression
+    else {
+        return Ok(None);
+    };
+    environment.evaluate_expression(&expression).map(Some)
+}
+
+/// Evaluates `program` in `environment` when it is a single expression, and returns `None`
+/// without evaluating it otherwise. The side effects of the expression, such as the assignments
+/// made by the functions it calls, are kept.
+pub fn evaluate_expression(
+    environment: &mut Environment,
+    program: Program,
+) -> Option<EvalResult<RuntimeValue>> {
+    let [Spanned {
+        node: Statement::Expression(expression),
+        ..
+    }] = &program.body[..]
+    else {
+        return None;
+    };
+    Some(environment.evaluate_expression(expression))
+}
@@ -0,0 +1,59 @@
+use crate::{diagnostics, diagnostics::Diagnostic, frontend::span::Spanned};
+
+/// Every input of the shell and every file it loaded, kept so that an error is shown against
+/// the source it comes from, even when it is raised later by a function defined there.
+///
+/// Each source is lexed from its own offset, past the end of the previous one, so the byte
+/// offsets of a span tell which source it belongs to.
+#[derive(Debug, Default)]
+pub struct Sources {
+    sources: Vec<Source>,
+}
+
+#[derive(Debug)]
+struct Source {
+    name: String,
+    text: String,
+    offset: usize,
+}
+
+impl Sources {
+    /// The offset the next source will be lexed from.
+    pub fn next_offset(&self) -> usize {
+        // The gap keeps a span at the very end of a source from being taken for the next one.
+        self.sources
+            .last()
+            .map_or(0, |source| source.offset + source.text.len() + 1)
+    }
+    /// Keeps `text`, named `name` in diagnostics, returning the offset to lex it from.
+    pub fn add(&mut self, name: &str, text: &str) -> usize {
+        let offset = self.next_offset();
+        self.sources.push(Source {
+            name: name.to_string(),
+            text: text.to_string(),
+            offset,
+        });
+        offset
+    }
+    /// The name and text of the source `offset` is in, and the offset the source starts at.
+    pub fn locate(&self, offset: usize) -> Option<(&str, &str, usize)> {
+        let source = self
+            .sources
+            .iter()
+            .rev()
+            .find(|source| source.offset <= offset)?;
+        Some((&source.name, &source.text, source.offset))
+    }
+    /// Prints every error of `errors` as a diagnostic, against the source it was found in.
+    pub fn emit<'a, E: 'a>(&self, errors: impl IntoIterator<Item = &'a Spanned<E>>)
+    where
+        Diagnostic: From<&'a Spanned<E>>,
+    {
+        for error in errors {
+            let (name, text, offset) = self
+                .locate(error.span.start)
+                .expect("errors come from sources of the session");
+            diagnostics::emit_at([error], name, text, offset);
+        }
+    }
+}
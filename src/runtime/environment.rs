@@ -1,45 +1,254 @@
-use crate::frontend::ast::{Expression, Program, Statement};
-use std::collections::HashMap;
+use crate::frontend::{
+    ast::{AssignmentTarget, Expression, FunctionDefinition, Program, Statement},
+    span::{Span, Spanned},
+};
+use std::{
+    cell::{Cell, RefCell},
+    collections::{BTreeMap, HashMap},
+    fmt::Display,
+    rc::Rc,
+};
 
-use super::values::RuntimeValue;
+use super::values::{Function, Map, RuntimeValue};
 
-#[derive(Debug, Clone)]
+/// How many function calls can be nested before [`EnvError::RecursionLimit`] is raised,
+/// well before the interpreter itself runs out of stack.
+pub const MAX_CALL_DEPTH: usize = 1000;
+
+#[derive(Debug, Clone, PartialEq)]
 pub enum EnvError {
     VarRedefining(String),
     VarNotFound(String),
+    /// An assignment to a name declared with `const`.
+    ConstAssignment(String),
+    /// A binary operator applied to values of types it doesn't support.
+    InvalidOperands {
+        operator: String,
+        left: &'static str,
+        right: &'static str,
+    },
+    /// `for ... in` over a value without elements.
+    NotIterable(&'static str),
+    /// A function called with a number of arguments different from its number of parameters.
+    ArityMismatch {
+        function: String,
+        expected: usize,
+        found: usize,
+    },
+    /// A call on a value that isn't a function.
+    NotCallable(&'static str),
+    /// An array index below zero.
+    NegativeIndex(i32),
+    /// An array index past the last element.
+    IndexOutOfBounds {
+        index: i32,
+        length: usize,
+    },
+    /// A map read at a key it doesn't have.
+    MissingKey(String),
+    /// `/` or `%` between ints, with a zero right operand.
+    DivisionByZero(String),
+    /// Int arithmetic whose result doesn't fit an int, in checked mode.
+    /// `operands` holds one value for unary operators and two for binary ones.
+    IntegerOverflow {
+        operator: String,
+        operands: Vec<i32>,
+    },
+    /// A condition that isn't a bool, in strict mode.
+    NonBoolCondition(&'static str),
+    /// An operator applied to a single value of a type it doesn't support, e.g. `&&` on an int.
+    InvalidOperand {
+        operator: String,
+        operand: &'static str,
+    },
+    /// A call nested in more than [`MAX_CALL_DEPTH`] other calls, usually from a recursion
+    /// that never stops.
+    RecursionLimit(String),
 }
 
-// VarRedefining: format!("Cannot declare variable {variable_name} as it is already defined.")
-//
-// VarNotFound: format!("Caannot resolve {variable_name} as it does not exist.")
+impl Display for EnvError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::VarRedefining(variable_name) => write!(
+                f,
+                "Cannot declare variable {variable_name} as it is already defined."
+            ),
+            Self::VarNotFound(variable_name) => {
+                write!(f, "Cannot resolve {variable_name} as it does not exist.")
+            }
+            Self::ConstAssignment(constant_name) => {
+                write!(f, "Cannot assign to {constant_name} as it is a constant.")
+            }
+            Self::InvalidOperands {
+                operator,
+                left,
+                right,
+            } => write!(f, "Cannot apply '{operator}' to {left} and {right}."),
+            Self::NotIterable(found) => write!(f, "Cannot iterate over {found}."),
+            Self::ArityMismatch {
+                function,
+                expected,
+                found,
+            } => write!(
+                f,
+                "Function {function} takes {expected} argument{} but {found} {} given.",
+                if *expected == 1 { "" } else { "s" },
+                if *found == 1 { "was" } else { "were" }
+            ),
+            Self::NotCallable(found) => write!(f, "Cannot call {found} as a function."),
+            Self::NegativeIndex(index) => {
+                write!(f, "Cannot index an array with the negative index {index}.")
+            }
+            Self::IndexOutOfBounds { index, length } => write!(
+                f,
+                "Index {index} is out of bounds for an array of length {length}."
+            ),
+            Self::MissingKey(key) => write!(f, "The map has no key \"{key}\"."),
+            Self::DivisionByZero(operator) => {
+                write!(f, "Cannot apply '{operator}' with a zero divisor.")
+            }
+            Self::IntegerOverflow { operator, operands } => match operands[..] {
+                [operand] => write!(f, "Integer overflow computing {operator}({operand})."),
+                [left, right] => write!(f, "Integer overflow computing {left} {operator} {right}."),
+                _ => write!(f, "Integer overflow computing '{operator}'."),
+            },
+            Self::NonBoolCondition(found) => {
+                write!(f, "Expected a bool as condition, but found {found}.")
+            }
+            Self::InvalidOperand { operator, operand } => {
+                write!(f, "Cannot apply '{operator}' to {operand}.")
+            }
+            Self::RecursionLimit(function) => write!(
+                f,
+                "Cannot call {function} as calls are nested more than {MAX_CALL_DEPTH} deep."
+            ),
+        }
+    }
+}
 
+/// A handle to a scope and, through its parent, to every scope enclosing it.
+///
+/// Scopes are reference-counted and shared: cloning an `Environment` gives another handle to
+/// the same variables, which is how functions keep the scope they were created in alive.
 #[derive(Debug, Clone)]
 pub struct Environment {
-    parent: Option<Box<Environment>>,
+    scope: Rc<RefCell<Scope>>,
+    /// When set, conditions must be bools instead of being tested for truthiness.
+    /// Child scopes inherit it from their parent.
+    strict_conditions: bool,
+    /// What int arithmetic does on overflow. Child scopes inherit it from their parent.
+    overflow_mode: OverflowMode,
+    /// How many calls are running, shared by every scope made from the same root.
+    call_depth: Rc<Cell<usize>>,
+}
+
+/// What int arithmetic does when its result doesn't fit an int.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum OverflowMode {
+    /// Raise an [`EnvError::IntegerOverflow`].
+    #[default]
+    Checked,
+    /// Wrap around, as two's complement arithmetic does.
+    Wrapping,
+    /// Clamp the result to the smallest or largest int.
+    Saturating,
+}
+
+#[derive(Debug)]
+struct Scope {
+    parent: Option<Environment>,
     variables: HashMap<String, RuntimeValue>,
     constants: HashMap<String, RuntimeValue>,
 }
 
 type EnvResult<T> = Result<T, EnvError>;
 
+/// An element of an array or an entry of a map, as designated by `target[index]` or
+/// `target.field`.
+enum Place {
+    /// Always within the bounds of the array.
+    Element(Rc<RefCell<Vec<RuntimeValue>>>, usize),
+    /// The key may not be in the map yet.
+    Entry(Rc<RefCell<Map>>, Rc<str>),
+}
+
+impl Place {
+    fn read(&self) -> EnvResult<RuntimeValue> {
+        match self {
+            Self::Element(elements, position) => Ok(elements.borrow()[*position].clone()),
+            Self::Entry(map, key) => match map.borrow().get(key) {
+                Some(value) => Ok(value.clone()),
+                None => Err(EnvError::MissingKey(key.to_string())),
+            },
+        }
+    }
+    fn write(&self, value: RuntimeValue) {
+        match self {
+            Self::Element(elements, position) => elements.borrow_mut()[*position] = value,
+            Self::Entry(map, key) => map.borrow_mut().insert(key, value),
+        }
+    }
+}
+
+/// How evaluating a statement ended, when it didn't raise an error.
+#[derive(Debug, Clone)]
+pub enum ControlFlow {
+    Normal,
+    /// A `break` is unwinding to the innermost loop.
+    Break,
+    /// A `continue` is unwinding to the innermost loop.
+    Continue,
+    /// A `return` is unwinding to the innermost function call.
+    Return(RuntimeValue),
+}
+
+/// Result of evaluating an AST node.
+/// Errors carry the span of the node that raised them.
+pub type EvalResult<T> = Result<T, Spanned<EnvError>>;
+
+/// Attaches `span` to a bare [`EnvError`].
+fn at<T>(result: EnvResult<T>, span: Span) -> EvalResult<T> {
+    result.map_err(|error| Spanned::new(error, span))
+}
+
 impl Environment {
-    pub fn create(parent: Option<Box<Self>>) -> Self {
+    pub fn create(parent: Option<Self>) -> Self {
         Self {
-            parent,
-            variables: HashMap::new(),
-            constants: HashMap::new(),
+            strict_conditions: parent.as_ref().is_some_and(|p| p.strict_conditions),
+            overflow_mode: parent
+                .as_ref()
+                .map_or_else(Default::default, |p| p.overflow_mode),
+            call_depth: parent
+                .as_ref()
+                .map_or_else(Default::default, |p| Rc::clone(&p.call_depth)),
+            scope: Rc::new(RefCell::new(Scope {
+                parent,
+                variables: HashMap::new(),
+                constants: HashMap::new(),
+            })),
         }
     }
+    pub fn set_strict_conditions(&mut self, strict_conditions: bool) -> &mut Self {
+        self.strict_conditions = strict_conditions;
+        self
+    }
+    pub fn set_overflow_mode(&mut self, overflow_mode: OverflowMode) -> &mut Self {
+        self.overflow_mode = overflow_mode;
+        self
+    }
     pub fn declare_constant(
         &mut self,
         constant_name: &str,
         value: RuntimeValue,
     ) -> EnvResult<&mut Self> {
-        if self.constants.contains_key(constant_name) || self.variables.contains_key(constant_name)
+        let mut scope = self.scope.borrow_mut();
+        if scope.constants.contains_key(constant_name)
+            || scope.variables.contains_key(constant_name)
         {
             return Err(EnvError::VarRedefining(constant_name.to_string()));
         }
-        self.constants.insert(constant_name.to_string(), value);
+        scope.constants.insert(constant_name.to_string(), value);
+        drop(scope);
         Ok(self)
     }
     pub fn declare_variable(
@@ -47,157 +256,668 @@ impl Environment {
         variable_name: &str,
         value: RuntimeValue,
     ) -> EnvResult<&mut Self> {
-        if self.variables.contains_key(variable_name) || self.constants.contains_key(variable_name)
+        let mut scope = self.scope.borrow_mut();
+        if scope.variables.contains_key(variable_name)
+            || scope.constants.contains_key(variable_name)
         {
             return Err(EnvError::VarRedefining(variable_name.to_string()));
         }
-        self.variables.insert(variable_name.to_string(), value);
+        scope.variables.insert(variable_name.to_string(), value);
+        drop(scope);
         Ok(self)
     }
     pub fn assign_variable(
         &mut self,
-        variable_name: &String,
+        variable_name: &str,
         value: RuntimeValue,
     ) -> EnvResult<&mut Self> {
         let environment = self.resolve_mut(variable_name)?;
         environment
+            .scope
+            .borrow_mut()
             .variables
             .insert(variable_name.to_string(), value);
         Ok(self)
     }
 
-    pub fn resolve(&self, variable_name: &String) -> Option<&Self> {
-        if self.variables.contains_key(variable_name) || self.constants.contains_key(variable_name)
-        {
-            return Some(self);
+    /// Every name visible from this scope, sorted, with its value and whether it is a constant.
+    /// Declarations of inner scopes shadow the ones of outer scopes.
+    pub fn bindings(&self) -> Vec<(String, RuntimeValue, bool)> {
+        let mut bindings = BTreeMap::new();
+        self.collect_bindings(&mut bindings);
+        bindings
+            .into_iter()
+            .map(|(name, (value, constant))| (name, value, constant))
+            .collect()
+    }
+    fn collect_bindings(&self, bindings: &mut BTreeMap<String, (RuntimeValue, bool)>) {
+        let scope = self.scope.borrow();
+        if let Some(parent) = &scope.parent {
+            parent.collect_bindings(bindings);
+        }
+        for (name, value) in &scope.variables {
+            bindings.insert(name.clone(), (value.clone(), false));
         }
-        if let None = self.parent {
-            return None;
+        for (name, value) in &scope.constants {
+            bindings.insert(name.clone(), (value.clone(), true));
         }
-        Some(self.parent.as_ref().unwrap().resolve(variable_name)?)
     }
-    /// TODO
-    /// Resolving mechanics don't get constant var.
-    pub fn resolve_mut(&mut self, variable_name: &String) -> EnvResult<&mut Self> {
-        if self.variables.contains_key(variable_name) {
-            return Ok(self);
+    /// The innermost scope declaring `variable_name`, starting from this one.
+    pub fn resolve(&self, variable_name: &str) -> Option<Self> {
+        let scope = self.scope.borrow();
+        if scope.variables.contains_key(variable_name)
+            || scope.constants.contains_key(variable_name)
+        {
+            return Some(self.clone());
         }
-        if let None = self.parent {
-            return Err(EnvError::VarNotFound(variable_name.to_string()));
+        scope.parent.as_ref()?.resolve(variable_name)
+    }
+    /// The innermost scope declaring `variable_name`, for assigning to it.
+    /// Fails if that declaration is a constant.
+    pub fn resolve_mut(&mut self, variable_name: &str) -> EnvResult<Self> {
+        let scope = self.scope.borrow();
+        if scope.variables.contains_key(variable_name) {
+            return Ok(self.clone());
+        }
+        if scope.constants.contains_key(variable_name) {
+            return Err(EnvError::ConstAssignment(variable_name.to_string()));
+        }
+        match scope.parent.clone() {
+            Some(mut parent) => parent.resolve_mut(variable_name),
+            None => Err(EnvError::VarNotFound(variable_name.to_string())),
         }
-        Ok(self.parent.as_mut().unwrap().resolve_mut(variable_name)?)
     }
-    pub fn evaluate(&mut self, ast_node: Statement) -> EnvResult<&mut Self> {
-        match ast_node {
+    pub fn evaluate(&mut self, ast_node: &Spanned<Statement>) -> EvalResult<ControlFlow> {
+        let span = ast_node.span;
+        match &ast_node.node {
             Statement::Expression(expression) => {
-                self.evaluate_expression(expression);
-                Ok(self)
+                self.evaluate_expression(expression)?;
+            }
+            Statement::Program(program) => {
+                for statement in &program.body {
+                    self.evaluate(statement)?;
+                }
             }
-            Statement::Program(program) => self.evaluate_program(program),
             Statement::VarDeclaration { identifier, value } => {
-                self.evaluate_variable_declaration(identifier.clone(), value)?;
-                self.evaluate_identifier(identifier).map(|_| self)
+                self.evaluate_variable_declaration(identifier, value.as_ref(), span)?;
             }
             Statement::ConstDeclaration { identifier, value } => {
-                self.evaluate_constant_declaration(identifier.clone(), value)?;
-                self.evaluate_identifier(identifier).map(|_| self)
+                self.evaluate_constant_declaration(identifier, value, span)?;
+            }
+            Statement::VarAssignment {
+                target: AssignmentTarget::Variable(identifier),
+                value,
+            } => {
+                self.evaluate_variable_assignment(identifier, value, span)?;
+            }
+            Statement::VarAssignment {
+                target: AssignmentTarget::Index { target, index },
+                value,
+            } => {
+                let place = self.evaluate_index(target, index)?;
+                place.write(self.evaluate_expression(value)?);
+            }
+            Statement::VarAssignment {
+                target: AssignmentTarget::Member { target, field },
+                value,
+            } => {
+                let place = self.evaluate_member(target, field, span)?;
+                place.write(self.evaluate_expression(value)?);
+            }
+            Statement::Block(body) => return self.evaluate_block(body),
+            Statement::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                if self.evaluate_condition(condition)? {
+                    return self.evaluate(then_branch);
+                } else if let Some(else_branch) = else_branch {
+                    return self.evaluate(else_branch);
+                }
+            }
+            Statement::While { condition, body } => {
+                while self.evaluate_condition(condition)? {
+                    match self.evaluate(body)? {
+                        ControlFlow::Break => break,
+                        control_flow @ ControlFlow::Return(_) => return Ok(control_flow),
+                        ControlFlow::Normal | ControlFlow::Continue => {}
+                    }
+                }
             }
-            Statement::VarAssignment { identifier, value } => {
-                self.evaluate_variable_assignment(identifier, value)
+            Statement::For {
+                variable,
+                iterable,
+                body,
+            } => {
+                let iterable_span = iterable.span;
+                let iterable = self.evaluate_expression(iterable)?;
+                let Some(items) = iterable.iterate() else {
+                    return Err(Spanned::new(
+                        EnvError::NotIterable(iterable.type_name()),
+                        iterable_span,
+                    ));
+                };
+                for item in items {
+                    let control_flow = self.with_child_scope(|scope| {
+                        at(scope.declare_variable(variable, item), span)?;
+                        scope.evaluate(body)
+                    })?;
+                    match control_flow {
+                        ControlFlow::Break => break,
+                        ControlFlow::Return(_) => return Ok(control_flow),
+                        ControlFlow::Normal | ControlFlow::Continue => {}
+                    }
+                }
             }
+            Statement::Break => return Ok(ControlFlow::Break),
+            Statement::Continue => return Ok(ControlFlow::Continue),
+            Statement::FunctionDeclaration(definition) => {
+                let name = definition.display_name();
+                let function = self.create_function(definition);
+                at(self.declare_variable(name, function), span)?;
+            }
+            Statement::Return(value) => {
+                let value = match value {
+                    Some(value) => self.evaluate_expression(value)?,
+                    None => RuntimeValue::NullValue,
+                };
+                return Ok(ControlFlow::Return(value));
+            }
+        }
+        Ok(ControlFlow::Normal)
+    }
+    /// Evaluates the condition of a branch, following truthiness unless in strict mode.
+    fn evaluate_condition(&mut self, condition: &Spanned<Expression>) -> EvalResult<bool> {
+        let span = condition.span;
+        match self.evaluate_expression(condition)? {
+            RuntimeValue::Bool(value) => Ok(value),
+            value if !self.strict_conditions => Ok(value.is_truthy()),
+            value => Err(Spanned::new(
+                EnvError::NonBoolCondition(value.type_name()),
+                span,
+            )),
         }
     }
-    pub fn evaluate_program(&mut self, program: Program) -> EnvResult<&mut Self> {
-        for statement in program.body {
+    /// Runs `f` in a fresh child scope of `self`.
+    /// Declarations made by `f` shadow the ones of `self` and are dropped once it returns,
+    /// unless a function created in the scope still holds it.
+    pub fn with_child_scope<T>(&self, f: impl FnOnce(&mut Environment) -> T) -> T {
+        f(&mut Environment::create(Some(self.clone())))
+    }
+    /// Evaluates `body` in a child scope, stopping early at a `break`, `continue` or `return`.
+    pub fn evaluate_block(&mut self, body: &[Spanned<Statement>]) -> EvalResult<ControlFlow> {
+        self.with_child_scope(|scope| {
+            for statement in body {
+                let control_flow = scope.evaluate(statement)?;
+                if !matches!(control_flow, ControlFlow::Normal) {
+                    return Ok(control_flow);
+                }
+            }
+            Ok(ControlFlow::Normal)
+        })
+    }
+    /// The parser rejects `break` and `continue` outside of loops and `return` outside of
+    /// functions, so a whole program always ends normally.
+    pub fn evaluate_program(&mut self, program: Program) -> EvalResult<&mut Self> {
+        for statement in &program.body {
             self.evaluate(statement)?;
         }
         Ok(self)
     }
-    pub fn evaluate_expression(&mut self, expression: Expression) -> EnvResult<RuntimeValue> {
-        match expression {
-            Expression::NumericLiteral(number) => Ok(RuntimeValue::NumberValue(number)),
-            Expression::Identifier(identifier) => self.evaluate_identifier(identifier),
+    pub fn evaluate_expression(
+        &mut self,
+        expression: &Spanned<Expression>,
+    ) -> EvalResult<RuntimeValue> {
+        let span = expression.span;
+        match &expression.node {
+            Expression::NumericLiteral(number) => Ok(RuntimeValue::NumberValue(*number)),
+            Expression::FloatLiteral(number) => Ok(RuntimeValue::Float(*number)),
+            Expression::StringLiteral(string) => Ok(RuntimeValue::String(string.as_str().into())),
+            Expression::BoolLiteral(value) => Ok(RuntimeValue::Bool(*value)),
+            Expression::NullLiteral => Ok(RuntimeValue::NullValue),
+            Expression::Identifier(identifier) => {
+                at(self.evaluate_identifier(identifier.clone()), span)
+            }
+            Expression::UnaryExpression { operand, operator } => {
+                let operand = self.evaluate_expression(operand)?;
+                at(self.evaluate_unary_operation(operand, operator), span)
+            }
+            Expression::Range {
+                start,
+                end,
+                inclusive,
+            } => {
+                let start = self.evaluate_expression(start)?;
+                let end = self.evaluate_expression(end)?;
+                match (start, end) {
+                    (RuntimeValue::NumberValue(start), RuntimeValue::NumberValue(end)) => {
+                        Ok(RuntimeValue::Range {
+                            start,
+                            end,
+                            inclusive: *inclusive,
+                        })
+                    }
+                    (start, end) => Err(Spanned::new(
+                        EnvError::InvalidOperands {
+                            operator: if *inclusive { "..=" } else { ".." }.to_string(),
+                            left: start.type_name(),
+                            right: end.type_name(),
+                        },
+                        span,
+                    )),
+                }
+            }
             Expression::BinaryExpression {
                 left,
                 right,
                 operator,
-            } => self.evaluate_binary_operation(left, right, operator),
+            } => self.evaluate_binary_operation(left, right, operator, span),
+            Expression::Function(definition) => Ok(self.create_function(definition)),
+            Expression::ArrayLiteral(elements) => {
+                let elements = elements
+                    .iter()
+                    .map(|element| self.evaluate_expression(element))
+                    .collect::<EvalResult<Vec<_>>>()?;
+                Ok(RuntimeValue::Array(Rc::new(RefCell::new(elements))))
+            }
+            Expression::MapLiteral(entries) => {
+                let mut map = Map::default();
+                for (key, value) in entries {
+                    map.insert(key, self.evaluate_expression(value)?);
+                }
+                Ok(RuntimeValue::Map(Rc::new(RefCell::new(map))))
+            }
+            Expression::Index { target, index } => {
+                at(self.evaluate_index(target, index)?.read(), span)
+            }
+            Expression::Member { target, field } => {
+                at(self.evaluate_member(target, field, span)?.read(), span)
+            }
+            Expression::Call { callee, arguments } => {
+                let callee = self.evaluate_expression(callee)?;
+                let arguments = arguments
+                    .iter()
+                    .map(|argument| self.evaluate_expression(argument))
+                    .collect::<EvalResult<Vec<_>>>()?;
+                self.call_function(callee, arguments, span)
+            }
         }
     }
+    /// Evaluates `target[index]` down to the place it designates, whether it is then read or
+    /// written. Arrays take int indices, which must be within bounds, and maps string keys.
+    fn evaluate_index(
+        &mut self,
+        target: &Spanned<Expression>,
+        index: &Spanned<Expression>,
+    ) -> EvalResult<Place> {
+        let span = target.span.to(index.span);
+        let target = self.evaluate_expression(target)?;
+        let index_span = index.span;
+        let index = self.evaluate_expression(index)?;
+        let (elements, index) = match (&target, &index) {
+            (RuntimeValue::Array(elements), RuntimeValue::NumberValue(index)) => (elements, *index),
+            (RuntimeValue::Map(map), RuntimeValue::String(key)) => {
+                return Ok(Place::Entry(Rc::clone(map), Rc::clone(key)))
+            }
+            _ => {
+                return Err(Spanned::new(
+                    EnvError::InvalidOperands {
+                        operator: "[]".to_string(),
+                        left: target.type_name(),
+                        right: index.type_name(),
+                    },
+                    span,
+                ))
+            }
+        };
+        let length = elements.borrow().len();
+        match usize::try_from(index) {
+            Ok(position) if position < length => Ok(Place::Element(Rc::clone(elements), position)),
+            Ok(_) => Err(Spanned::new(
+                EnvError::IndexOutOfBounds { index, length },
+                index_span,
+            )),
+            Err(_) => Err(Spanned::new(EnvError::NegativeIndex(index), index_span)),
+        }
+    }
+    /// Evaluates `target.field`, which is the same as `target["field"]`, down to the place it
+    /// designates.
+    fn evaluate_member(
+        &mut self,
+        target: &Spanned<Expression>,
+        field: &str,
+        span: Span,
+    ) -> EvalResult<Place> {
+        match self.evaluate_expression(target)? {
+            RuntimeValue::Map(map) => Ok(Place::Entry(map, field.into())),
+            target => Err(Spanned::new(
+                EnvError::InvalidOperand {
+                    operator: ".".to_string(),
+                    operand: target.type_name(),
+                },
+                span,
+            )),
+        }
+    }
+    /// A function value capturing this scope.
+    fn create_function(&self, definition: &Rc<FunctionDefinition>) -> RuntimeValue {
+        RuntimeValue::Function(Rc::new(Function {
+            definition: Rc::clone(definition),
+            closure: self.clone(),
+        }))
+    }
+    /// Calls `callee` with `arguments` already evaluated, in a fresh scope where each parameter
+    /// is declared with its argument. The call scope is a child of the scope the function was
+    /// created in, not of the caller's.
+    /// Returns the value of the first `return` reached, or `null` if the body ends without one.
+    pub fn call_function(
+        &mut self,
+        callee: RuntimeValue,
+        arguments: Vec<RuntimeValue>,
+        span: Span,
+    ) -> EvalResult<RuntimeValue> {
+        let RuntimeValue::Function(function) = callee else {
+            return Err(Spanned::new(
+                EnvError::NotCallable(callee.type_name()),
+                span,
+            ));
+        };
+        let definition = &function.definition;
+        if arguments.len() != definition.parameters.len() {
+            return Err(Spanned::new(
+                EnvError::ArityMismatch {
+                    function: definition.display_name().to_string(),
+                    expected: definition.parameters.len(),
+                    found: arguments.len(),
+                },
+                span,
+            ));
+        }
+        // The calls made by the body are counted by the scopes under the closure.
+        let call_depth = &function.closure.call_depth;
+        if call_depth.get() >= MAX_CALL_DEPTH {
+            return Err(Spanned::new(
+                EnvError::RecursionLimit(definition.display_name().to_string()),
+                span,
+            ));
+        }
+        call_depth.set(call_depth.get() + 1);
+        let result = function.closure.with_child_scope(|scope| {
+            for (parameter, argument) in definition.parameters.iter().zip(arguments) {
+                at(scope.declare_variable(parameter, argument), span)?;
+            }
+            for statement in &definition.body {
+                if let ControlFlow::Return(value) = scope.evaluate(statement)? {
+                    return Ok(value);
+                }
+            }
+            Ok(RuntimeValue::NullValue)
+        });
+        call_depth.set(call_depth.get() - 1);
+        result
+    }
     pub fn evaluate_identifier(&mut self, identifier: String) -> EnvResult<RuntimeValue> {
-        if let Some(environment) = self.resolve(&identifier) {
-            return Ok(environment.variables.get(&identifier).unwrap().clone());
-        } else if let Some(environment) = self.resolve(&identifier) {
-            return Ok(environment.constants.get(&identifier).unwrap().clone());
-        } else {
+        let Some(environment) = self.resolve(&identifier) else {
             return Err(EnvError::VarNotFound(identifier));
-        }
-        // TODO other preserved words
+        };
+        let scope = environment.scope.borrow();
+        let value = scope
+            .variables
+            .get(&identifier)
+            .or_else(|| scope.constants.get(&identifier));
+        // `resolve` only returns scopes declaring the identifier as one or the other.
+        Ok(value.unwrap().clone())
     }
 
+    fn evaluate_unary_operation(
+        &self,
+        operand: RuntimeValue,
+        operator: &str,
+    ) -> EnvResult<RuntimeValue> {
+        match (operator, operand) {
+            ("-", RuntimeValue::NumberValue(number)) => {
+                let negated = match self.overflow_mode {
+                    OverflowMode::Checked => number.checked_neg(),
+                    OverflowMode::Wrapping => Some(number.wrapping_neg()),
+                    OverflowMode::Saturating => Some(number.saturating_neg()),
+                };
+                match negated {
+                    Some(negated) => Ok(RuntimeValue::NumberValue(negated)),
+                    None => Err(EnvError::IntegerOverflow {
+                        operator: operator.to_string(),
+                        operands: vec![number],
+                    }),
+                }
+            }
+            ("-", RuntimeValue::Float(number)) => Ok(RuntimeValue::Float(-number)),
+            ("+", operand @ (RuntimeValue::NumberValue(_) | RuntimeValue::Float(_))) => Ok(operand),
+            ("!", RuntimeValue::Bool(value)) => Ok(RuntimeValue::Bool(!value)),
+            (_, operand) => Err(EnvError::InvalidOperand {
+                operator: operator.to_string(),
+                operand: operand.type_name(),
+            }),
+        }
+    }
     fn evaluate_binary_operation(
         &mut self,
-        left: Box<Expression>,
-        right: Box<Expression>,
-        operator: String,
-    ) -> EnvResult<RuntimeValue> {
-        let left = self.evaluate_expression(*left)?;
-        let right = self.evaluate_expression(*right)?;
+        left: &Spanned<Expression>,
+        right: &Spanned<Expression>,
+        operator: &str,
+        span: Span,
+    ) -> EvalResult<RuntimeValue> {
+        if operator == "&&" || operator == "||" {
+            return self.evaluate_logical_operation(left, right, operator);
+        }
+        let left = self.evaluate_expression(left)?;
+        let right = self.evaluate_expression(right)?;
+        match operator {
+            "==" => return Ok(RuntimeValue::Bool(left.equals(&right))),
+            "!=" => return Ok(RuntimeValue::Bool(!left.equals(&right))),
+            "<" | "<=" | ">" | ">=" => {
+                return at(
+                    Environment::evaluate_comparison(&left, &right, operator),
+                    span,
+                )
+            }
+            _ => {}
+        }
         match (left, right) {
             (
                 RuntimeValue::NumberValue(left_number_value),
                 RuntimeValue::NumberValue(right_number_value),
-            ) => Ok(RuntimeValue::NumberValue(
-                Environment::evaluate_numeric_binary_operation(
+            ) => at(
+                self.evaluate_numeric_binary_operation(
                     left_number_value,
                     right_number_value,
                     operator,
                 ),
+                span,
+            )
+            .map(RuntimeValue::NumberValue),
+            (RuntimeValue::Float(left_number_value), RuntimeValue::Float(right_number_value)) => {
+                Ok(RuntimeValue::Float(
+                    Environment::evaluate_float_binary_operation(
+                        left_number_value,
+                        right_number_value,
+                        operator,
+                    ),
+                ))
+            }
+            (
+                RuntimeValue::NumberValue(left_number_value),
+                RuntimeValue::Float(right_number_value),
+            ) => Ok(RuntimeValue::Float(
+                Environment::evaluate_float_binary_operation(
+                    left_number_value as f64,
+                    right_number_value,
+                    operator,
+                ),
+            )),
+            (
+                RuntimeValue::Float(left_number_value),
+                RuntimeValue::NumberValue(right_number_value),
+            ) => Ok(RuntimeValue::Float(
+                Environment::evaluate_float_binary_operation(
+                    left_number_value,
+                    right_number_value as f64,
+                    operator,
+                ),
+            )),
+            (RuntimeValue::String(left_string), RuntimeValue::String(right_string))
+                if operator == "+" =>
+            {
+                Ok(RuntimeValue::String(
+                    format!("{left_string}{right_string}").into(),
+                ))
+            }
+            (left, right) => Err(Spanned::new(
+                EnvError::InvalidOperands {
+                    operator: operator.to_string(),
+                    left: left.type_name(),
+                    right: right.type_name(),
+                },
+                span,
             )),
-            (_, _) => todo!("Not implemented: evaluating other types of binary operations"),
         }
     }
-    fn evaluate_numeric_binary_operation(left: i32, right: i32, operator: String) -> i32 {
-        let result = match operator.as_str() {
+    /// `&&` and `||` take bools, and only evaluate `right` when `left` doesn't already decide the
+    /// result.
+    fn evaluate_logical_operation(
+        &mut self,
+        left: &Spanned<Expression>,
+        right: &Spanned<Expression>,
+        operator: &str,
+    ) -> EvalResult<RuntimeValue> {
+        let mut evaluate_operand = |operand: &Spanned<Expression>| {
+            let span = operand.span;
+            match self.evaluate_expression(operand)? {
+                RuntimeValue::Bool(value) => Ok(value),
+                value => Err(Spanned::new(
+                    EnvError::InvalidOperand {
+                        operator: operator.to_string(),
+                        operand: value.type_name(),
+                    },
+                    span,
+                )),
+            }
+        };
+        let left = evaluate_operand(left)?;
+        let short_circuits = match operator {
+            "&&" => !left,
+            _ => left,
+        };
+        if short_circuits {
+            return Ok(RuntimeValue::Bool(left));
+        }
+        Ok(RuntimeValue::Bool(evaluate_operand(right)?))
+    }
+    fn evaluate_comparison(
+        left: &RuntimeValue,
+        right: &RuntimeValue,
+        operator: &str,
+    ) -> EnvResult<RuntimeValue> {
+        let Some(ordering) = left.compare(right) else {
+            return Err(EnvError::InvalidOperands {
+                operator: operator.to_string(),
+                left: left.type_name(),
+                right: right.type_name(),
+            });
+        };
+        Ok(RuntimeValue::Bool(ordering.is_some_and(
+            |ordering| match operator {
+                "<" => ordering.is_lt(),
+                "<=" => ordering.is_le(),
+                ">" => ordering.is_gt(),
+                ">=" => ordering.is_ge(),
+                _ => panic!("Unexpected operator: {}", operator),
+            },
+        )))
+    }
+    /// Arithmetic between two ints stays in ints: `/` is integer division truncating towards
+    /// zero, and `%` takes the sign of the left operand.
+    /// Overflows are handled according to the overflow mode, but dividing by zero is always an
+    /// error.
+    fn evaluate_numeric_binary_operation(
+        &self,
+        left: i32,
+        right: i32,
+        operator: &str,
+    ) -> EnvResult<i32> {
+        if matches!(operator, "/" | "%") && right == 0 {
+            return Err(EnvError::DivisionByZero(operator.to_string()));
+        }
+        let result = match self.overflow_mode {
+            OverflowMode::Checked => match operator {
+                "+" => left.checked_add(right),
+                "-" => left.checked_sub(right),
+                "*" => left.checked_mul(right),
+                "/" => left.checked_div(right),
+                "%" => left.checked_rem(right),
+                _ => panic!("Unexpected operator: {}", operator),
+            },
+            OverflowMode::Wrapping => Some(match operator {
+                "+" => left.wrapping_add(right),
+                "-" => left.wrapping_sub(right),
+                "*" => left.wrapping_mul(right),
+                "/" => left.wrapping_div(right),
+                "%" => left.wrapping_rem(right),
+                _ => panic!("Unexpected operator: {}", operator),
+            }),
+            OverflowMode::Saturating => Some(match operator {
+                "+" => left.saturating_add(right),
+                "-" => left.saturating_sub(right),
+                "*" => left.saturating_mul(right),
+                "/" => left.saturating_div(right),
+                // Only `i32::MIN % -1` overflows, and its remainder is 0 anyway.
+                "%" => left.wrapping_rem(right),
+                _ => panic!("Unexpected operator: {}", operator),
+            }),
+        };
+        result.ok_or_else(|| EnvError::IntegerOverflow {
+            operator: operator.to_string(),
+            operands: vec![left, right],
+        })
+    }
+    /// As soon as one operand is a float, the int one is promoted and `/` is true division.
+    fn evaluate_float_binary_operation(left: f64, right: f64, operator: &str) -> f64 {
+        match operator {
             "+" => left + right,
             "-" => left - right,
             "*" => left * right,
             "/" => left / right,
             "%" => left % right,
             _ => panic!("Unexpected operator: {}", operator),
-        };
-        result
+        }
     }
     pub fn evaluate_variable_declaration(
         &mut self,
-        identifier: String,
-        value: Option<Expression>,
-    ) -> EnvResult<&mut Self> {
+        identifier: &str,
+        value: Option<&Spanned<Expression>>,
+        span: Span,
+    ) -> EvalResult<&mut Self> {
         let value = match value {
             Some(value) => self.evaluate_expression(value)?,
-            None => RuntimeValue::NullValue
+            None => RuntimeValue::NullValue,
         };
-        self.declare_variable(identifier.as_str(), value)
+        at(self.declare_variable(identifier, value), span)
     }
     pub fn evaluate_constant_declaration(
         &mut self,
-        identifier: String,
-        value: Expression,
-    ) -> EnvResult<&mut Self> {
+        identifier: &str,
+        value: &Spanned<Expression>,
+        span: Span,
+    ) -> EvalResult<&mut Self> {
         let value = self.evaluate_expression(value)?;
-        self.declare_constant(identifier.as_str(), value)
+        at(self.declare_constant(identifier, value), span)
     }
     pub fn evaluate_variable_assignment(
         &mut self,
-        identifier: String,
-        value: Expression,
-    ) -> EnvResult<&mut Self> {
-        let value = self.evaluate_expression(value);
-        let var_ref = match self.variables.get_mut(&identifier) {
-            Some(x) => x,
-            None => return Err(EnvError::VarNotFound(identifier)),
-        };
-        *var_ref = value?;
-        Ok(self)
+        identifier: &str,
+        value: &Spanned<Expression>,
+        span: Span,
+    ) -> EvalResult<&mut Self> {
+        let value = self.evaluate_expression(value)?;
+        at(self.assign_variable(identifier, value), span)
     }
 }
@@ -1,6 +1,306 @@
-#[derive(Debug, Clone, Copy)]
+use std::{
+    cell::RefCell,
+    cmp::Ordering,
+    collections::HashMap,
+    fmt::{self, Debug, Display},
+    rc::Rc,
+};
+
+use super::environment::Environment;
+use crate::frontend::ast::FunctionDefinition;
+
+#[derive(Debug, Clone)]
 pub enum RuntimeValue {
     NullValue,
     NumberValue(i32),
+    Float(f64),
     Bool(bool),
+    /// Strings are immutable, so values share the same buffer when copied around.
+    String(Rc<str>),
+    /// `start..end`, or `start..=end` when `inclusive`.
+    Range {
+        start: i32,
+        end: i32,
+        inclusive: bool,
+    },
+    Function(Rc<Function>),
+    /// Arrays are shared and mutable: copying an array value, as in `let b = a;` or when
+    /// passing it to a function, gives another reference to the same elements, so `b[0] = 1`
+    /// is also seen through `a`.
+    Array(Rc<RefCell<Vec<RuntimeValue>>>),
+    /// Maps from string keys to values, shared and mutable like arrays.
+    Map(Rc<RefCell<Map>>),
+}
+
+/// The entries of a map, kept in insertion order so that iterating over it is deterministic.
+#[derive(Debug, Clone, Default)]
+pub struct Map {
+    entries: Vec<(Rc<str>, RuntimeValue)>,
+    /// Position of each key in `entries`.
+    positions: HashMap<Rc<str>, usize>,
+}
+
+impl Map {
+    pub fn get(&self, key: &str) -> Option<&RuntimeValue> {
+        Some(&self.entries[*self.positions.get(key)?].1)
+    }
+    /// Replaces the value of `key` in place, or adds it after the other entries.
+    pub fn insert(&mut self, key: &str, value: RuntimeValue) {
+        match self.positions.get(key) {
+            Some(&position) => self.entries[position].1 = value,
+            None => {
+                let key: Rc<str> = key.into();
+                self.positions.insert(Rc::clone(&key), self.entries.len());
+                self.entries.push((key, value));
+            }
+        }
+    }
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+    /// The entries, in insertion order.
+    pub fn iter(&self) -> impl Iterator<Item = (&Rc<str>, &RuntimeValue)> {
+        self.entries.iter().map(|(key, value)| (key, value))
+    }
+}
+
+/// Values are written the way they would be in the source where possible, so strings are
+/// quoted. An array or map containing itself is shown as `[...]` or `{...}` where it recurs.
+impl Display for RuntimeValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.write(f, &mut Vec::new())
+    }
+}
+
+impl RuntimeValue {
+    /// Writes the value, `enclosing` holding the arrays and maps being written around it.
+    fn write(&self, f: &mut fmt::Formatter<'_>, enclosing: &mut Vec<*const ()>) -> fmt::Result {
+        match self {
+            Self::NullValue => write!(f, "null"),
+            Self::NumberValue(number) => write!(f, "{number}"),
+            // Unlike `Display`, `Debug` keeps the `.0` of whole floats.
+            Self::Float(number) => write!(f, "{number:?}"),
+            Self::Bool(value) => write!(f, "{value}"),
+            Self::String(string) => write!(f, "{:?}", &**string),
+            Self::Range {
+                start,
+                end,
+                inclusive,
+            } => write!(f, "{start}..{}{end}", if *inclusive { "=" } else { "" }),
+            Self::Function(function) => write!(f, "{function:?}"),
+            Self::Array(elements) => {
+                let pointer = Rc::as_ptr(elements) as *const ();
+                if enclosing.contains(&pointer) {
+                    return write!(f, "[...]");
+                }
+                enclosing.push(pointer);
+                write!(f, "[")?;
+                for (position, element) in elements.borrow().iter().enumerate() {
+                    if position > 0 {
+                        write!(f, ", ")?;
+                    }
+                    element.write(f, enclosing)?;
+                }
+                enclosing.pop();
+                write!(f, "]")
+            }
+            Self::Map(map) => {
+                let pointer = Rc::as_ptr(map) as *const ();
+                if enclosing.contains(&pointer) {
+                    return write!(f, "{{...}}");
+                }
+                enclosing.push(pointer);
+                write!(f, "{{")?;
+                for (position, (key, value)) in map.borrow().iter().enumerate() {
+                    if position > 0 {
+                        write!(f, ", ")?;
+                    }
+                    let is_identifier = key.starts_with(|c: char| c.is_alphabetic() || c == '_')
+                        && key.chars().all(|c| c.is_alphanumeric() || c == '_');
+                    if is_identifier {
+                        write!(f, "{key}: ")?;
+                    } else {
+                        write!(f, "{:?}: ", &**key)?;
+                    }
+                    value.write(f, enclosing)?;
+                }
+                enclosing.pop();
+                write!(f, "}}")
+            }
+        }
+    }
+}
+
+/// A function value: its definition and the scope it was created in.
+///
+/// The scope is shared rather than copied, so the function sees later changes to the variables
+/// it captured, and its own assignments to them are visible outside.
+/// A function stored in a scope it captured forms a reference cycle, which is never freed.
+pub struct Function {
+    pub definition: Rc<FunctionDefinition>,
+    pub closure: Environment,
+}
+
+/// Only shows the signature, since the captured scope may contain the function itself.
+impl Debug for Function {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "fn {}({})",
+            self.definition.display_name(),
+            self.definition.parameters.join(", ")
+        )
+    }
+}
+
+impl RuntimeValue {
+    /// Name of the value's type, as shown in error messages.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Self::NullValue => "null",
+            Self::NumberValue(_) => "int",
+            Self::Float(_) => "float",
+            Self::Bool(_) => "bool",
+            Self::String(_) => "string",
+            Self::Range { .. } => "range",
+            Self::Function(_) => "function",
+            Self::Array(_) => "array",
+            Self::Map(_) => "map",
+        }
+    }
+    /// Truthiness, as used by conditions outside of strict mode: `null`, `false`, zero, NaN and
+    /// the empty string are falsy, and every other value is truthy.
+    pub fn is_truthy(&self) -> bool {
+        match self {
+            Self::NullValue => false,
+            Self::NumberValue(number) => *number != 0,
+            Self::Float(number) => *number != 0.0 && !number.is_nan(),
+            Self::Bool(value) => *value,
+            Self::String(string) => !string.is_empty(),
+            Self::Range { .. } | Self::Function(_) | Self::Array(_) | Self::Map(_) => true,
+        }
+    }
+    /// The value of ints and floats as a float, for arithmetic and comparisons mixing the two.
+    pub fn as_float(&self) -> Option<f64> {
+        match self {
+            Self::NumberValue(number) => Some(*number as f64),
+            Self::Float(number) => Some(*number),
+            _ => None,
+        }
+    }
+    /// Equality as seen by `==`: ints and floats compare by numeric value, arrays and maps by
+    /// their elements, and values of other different types are never equal.
+    /// The order of their entries doesn't matter for maps.
+    /// Arrays and maps that contain themselves are equal when they are alike wherever compared.
+    pub fn equals(&self, other: &Self) -> bool {
+        self.equals_within(other, &mut Vec::new())
+    }
+    /// Compares the values, `comparing` holding the pairs of arrays and maps being compared
+    /// around them. A pair compared again is deemed equal, the outer comparison deciding.
+    fn equals_within(&self, other: &Self, comparing: &mut Vec<(*const (), *const ())>) -> bool {
+        match (self, other) {
+            (Self::NullValue, Self::NullValue) => true,
+            (Self::NumberValue(left), Self::NumberValue(right)) => left == right,
+            (Self::Bool(left), Self::Bool(right)) => left == right,
+            (Self::String(left), Self::String(right)) => left == right,
+            (
+                Self::Range {
+                    start,
+                    end,
+                    inclusive,
+                },
+                Self::Range {
+                    start: other_start,
+                    end: other_end,
+                    inclusive: other_inclusive,
+                },
+            ) => (start, end, inclusive) == (other_start, other_end, other_inclusive),
+            (Self::Function(left), Self::Function(right)) => Rc::ptr_eq(left, right),
+            (Self::Array(left), Self::Array(right)) => {
+                let pair = (
+                    Rc::as_ptr(left) as *const (),
+                    Rc::as_ptr(right) as *const (),
+                );
+                if Rc::ptr_eq(left, right) || comparing.contains(&pair) {
+                    return true;
+                }
+                comparing.push(pair);
+                let (left, right) = (left.borrow(), right.borrow());
+                let equal = left.len() == right.len()
+                    && left
+                        .iter()
+                        .zip(right.iter())
+                        .all(|(l, r)| l.equals_within(r, comparing));
+                comparing.pop();
+                equal
+            }
+            (Self::Map(left), Self::Map(right)) => {
+                let pair = (
+                    Rc::as_ptr(left) as *const (),
+                    Rc::as_ptr(right) as *const (),
+                );
+                if Rc::ptr_eq(left, right) || comparing.contains(&pair) {
+                    return true;
+                }
+                comparing.push(pair);
+                let (left, right) = (left.borrow(), right.borrow());
+                let equal = left.len() == right.len()
+                    && left.iter().all(|(key, l)| {
+                        right
+                            .get(key)
+                            .is_some_and(|r| l.equals_within(r, comparing))
+                    });
+                comparing.pop();
+                equal
+            }
+            (left, right) => match (left.as_float(), right.as_float()) {
+                (Some(left), Some(right)) => left == right,
+                _ => false,
+            },
+        }
+    }
+    /// Ordering as seen by `<` and friends, defined between numbers and between strings.
+    /// `Some(None)` means the values are comparable but unordered, as with a NaN.
+    pub fn compare(&self, other: &Self) -> Option<Option<Ordering>> {
+        match (self, other) {
+            (Self::NumberValue(left), Self::NumberValue(right)) => Some(Some(left.cmp(right))),
+            (Self::String(left), Self::String(right)) => Some(Some(left.cmp(right))),
+            (left, right) => Some(left.as_float()?.partial_cmp(&right.as_float()?)),
+        }
+    }
+    /// The iteration protocol used by `for ... in`: ranges yield their ints, strings their
+    /// characters, as one-character strings, arrays their elements and maps their keys, in
+    /// insertion order. Arrays and maps are iterated as they were when the loop started.
+    /// Returns `None` for values that can't be iterated.
+    pub fn iterate(&self) -> Option<Box<dyn Iterator<Item = RuntimeValue>>> {
+        match self {
+            Self::Range {
+                start,
+                end,
+                inclusive: false,
+            } => Some(Box::new((*start..*end).map(Self::NumberValue))),
+            Self::Range {
+                start,
+                end,
+                inclusive: true,
+            } => Some(Box::new((*start..=*end).map(Self::NumberValue))),
+            Self::String(string) => {
+                let characters: Vec<RuntimeValue> = string
+                    .chars()
+                    .map(|c| Self::String(c.to_string().into()))
+                    .collect();
+                Some(Box::new(characters.into_iter()))
+            }
+            Self::Array(elements) => Some(Box::new(elements.borrow().clone().into_iter())),
+            Self::Map(map) => {
+                let keys: Vec<RuntimeValue> = map
+                    .borrow()
+                    .iter()
+                    .map(|(key, _)| Self::String(Rc::clone(key)))
+                    .collect();
+                Some(Box::new(keys.into_iter()))
+            }
+            _ => None,
+        }
+    }
 }
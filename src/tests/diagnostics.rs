@@ -0,0 +1,73 @@
+use crate::{
+    diagnostics::{explain, Diagnostic, ERROR_CODES},
+    frontend::parser::Parser,
+    runtime::environment::Environment,
+};
+
+#[test]
+fn renders_runtime_error_with_snippet() {
+    let source = "let a = 1;\nlet b = a + missing;\n";
+    let program = Parser::initialize(source.to_string())
+        .unwrap()
+        .produce_ast()
+        .unwrap();
+    let error = Environment::create(None)
+        .evaluate_program(program)
+        .map(|_| ())
+        .unwrap_err();
+    let rendered = Diagnostic::from(&error).render("script.awa", source, false);
+    assert_eq!(
+        rendered,
+        "\
+error[E0001]: Cannot resolve missing as it does not exist.
+ --> script.awa:2:13
+  |
+2 | let b = a + missing;
+  |             ^^^^^^^ not found in this scope
+  = help: declare it first: `let missing = ...;`
+"
+    );
+}
+
+#[test]
+fn renders_eof_with_single_caret() {
+    let source = "let a =";
+    let errors = Parser::initialize(source.to_string())
+        .unwrap()
+        .produce_ast()
+        .unwrap_err();
+    let rendered = Diagnostic::from(&errors[0]).render("<shell>", source, false);
+    assert!(rendered.starts_with("error[E0202]"));
+    assert!(rendered.contains("\n1 | let a =\n  |        ^ input ends here\n"));
+}
+
+#[test]
+fn every_code_is_explained_once() {
+    for error_code in ERROR_CODES {
+        assert!(!error_code.explanation.is_empty());
+        assert_eq!(explain(error_code.code).unwrap().code, error_code.code);
+    }
+    assert!(explain("e0001").is_some());
+    assert!(explain("E9999").is_none());
+}
+
+#[test]
+fn labels_name_the_type_found() {
+    let label = |source: &str| {
+        let program = Parser::initialize(source.to_string())
+            .unwrap()
+            .produce_ast()
+            .unwrap();
+        let error = Environment::create(None)
+            .set_strict_conditions(true)
+            .evaluate_program(program)
+            .map(|_| ())
+            .unwrap_err();
+        Diagnostic::from(&error).label
+    };
+    assert_eq!(label("!5"), Some("found int".to_string()));
+    assert_eq!(label("if 1 {}"), Some("found int".to_string()));
+    assert_eq!(label("while 1 {}"), Some("found int".to_string()));
+    assert_eq!(label("let a = 1;\na(2);"), Some("found int".to_string()));
+    assert_eq!(label("for i in 3 { }"), Some("found int".to_string()));
+}
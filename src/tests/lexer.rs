@@ -1,16 +1,17 @@
 use crate::{frontend::parser::Parser, runtime::environment::Environment};
 
 fn standard_test(content: &str) {
-    let mut tokens = Parser::initialize(content.to_string());
+    let mut tokens = Parser::initialize(content.to_string()).unwrap();
     dbg!(&tokens);
-    let program = tokens.produce_ast();
+    let program = tokens.produce_ast().unwrap();
     dbg!(&program);
     let mut environment = Environment::create(None);
-    let result = environment.evaluate_program(program).map(|x| ());
+    let result = environment.evaluate_program(program).map(|_| ());
     dbg!(environment.clone());
     dbg!(&result);
 }
 
+#[allow(dead_code)]
 fn test1() {
     let content = 
 r#"a = 3; c = 5;
@@ -29,3 +30,163 @@ let d = a + c * 2;
 "#;
     standard_test(content);
 }
+
+#[test]
+fn token_spans() {
+    use crate::frontend::{
+        lexer::{tokenize, Token},
+        span::Span,
+    };
+
+    let tokens = tokenize("let a =\n  12;".to_string()).unwrap();
+    let spans: Vec<(&Token, Span)> = tokens.iter().map(|t| (&t.node, t.span)).collect();
+    assert_eq!(spans[0], (&Token::Let, Span::new(0, 3, 1, 1)));
+    assert_eq!(
+        spans[1],
+        (&Token::Identifier("a".to_string()), Span::new(4, 5, 1, 5))
+    );
+    assert_eq!(
+        spans[3],
+        (&Token::Number("12".to_string()), Span::new(10, 12, 2, 3))
+    );
+    assert_eq!(spans[5], (&Token::Eof, Span::new(13, 13, 2, 6)));
+}
+
+#[test]
+fn lex_errors_are_all_reported() {
+    use crate::frontend::lexer::{tokenize, LexError};
+
+    let errors = tokenize("let a = 1 $ 2;\nlet b = 12ab @;\n99999999999;".to_string()).unwrap_err();
+    let errors: Vec<(LexError, usize, usize)> = errors
+        .into_iter()
+        .map(|e| (e.node, e.span.line, e.span.column))
+        .collect();
+    assert_eq!(
+        errors,
+        vec![
+            (LexError::UnexpectedCharacter('$'), 1, 11),
+            (LexError::InvalidNumber("12ab".to_string()), 2, 9),
+            (LexError::UnexpectedCharacter('@'), 2, 14),
+            (LexError::InvalidNumber("99999999999".to_string()), 3, 1),
+        ]
+    );
+}
+
+#[test]
+fn string_literals() {
+    use crate::frontend::lexer::{tokenize, LexError, Token};
+
+    let tokens = tokenize(r#""a\"b\\c\n\u{1F600}""#.to_string()).unwrap();
+    assert_eq!(
+        tokens[0].node,
+        Token::String("a\"b\\c\n\u{1F600}".to_string())
+    );
+
+    let errors = tokenize("\"bad \\q\" \"open".to_string()).unwrap_err();
+    let errors: Vec<(LexError, usize)> = errors
+        .into_iter()
+        .map(|e| (e.node, e.span.column))
+        .collect();
+    assert_eq!(
+        errors,
+        vec![
+            (LexError::InvalidEscape("\\q".to_string()), 1),
+            (LexError::UnterminatedLiteral, 10),
+        ]
+    );
+}
+
+#[test]
+fn float_literals() {
+    use crate::frontend::lexer::{tokenize, LexError, Token};
+
+    let tokens: Vec<Token> = tokenize("1.5 2e10 3E+2 4.25e-3 7".to_string())
+        .unwrap()
+        .into_iter()
+        .map(|t| t.node)
+        .collect();
+    assert_eq!(
+        tokens,
+        vec![
+            Token::Float("1.5".to_string()),
+            Token::Float("2e10".to_string()),
+            Token::Float("3E+2".to_string()),
+            Token::Float("4.25e-3".to_string()),
+            Token::Number("7".to_string()),
+            Token::Eof,
+        ]
+    );
+
+    let errors: Vec<LexError> = tokenize("1e 2.5x 1e999".to_string())
+        .unwrap_err()
+        .into_iter()
+        .map(|e| e.node)
+        .collect();
+    assert_eq!(
+        errors,
+        vec![
+            LexError::InvalidNumber("1e".to_string()),
+            LexError::InvalidNumber("2.5x".to_string()),
+            LexError::InvalidNumber("1e999".to_string()),
+        ]
+    );
+}
+
+#[test]
+fn comments_are_skipped() {
+    use crate::frontend::lexer::{tokenize, LexError, Token};
+
+    let source = "let a = 1; // trailing\n/* outer /* nested */ still */ a / 2";
+    let tokens: Vec<Token> = tokenize(source.to_string())
+        .unwrap()
+        .into_iter()
+        .map(|t| t.node)
+        .collect();
+    assert_eq!(tokens.len(), 9);
+    assert_eq!(tokens[5], Token::Identifier("a".to_string()));
+
+    let errors = tokenize("1;\n  /* a /* b */".to_string()).unwrap_err();
+    assert_eq!(errors[0].node, LexError::UnterminatedComment);
+    assert_eq!((errors[0].span.line, errors[0].span.column), (2, 3));
+}
+
+#[test]
+fn two_character_operators() {
+    use crate::frontend::lexer::tokenize;
+
+    let tokens: Vec<String> = tokenize("a == b != c <= d < e >= f > g && h || i = j".to_string())
+        .unwrap()
+        .into_iter()
+        .map(|t| t.node.to_string())
+        .collect();
+    assert_eq!(
+        tokens.join(" "),
+        "a == b != c <= d < e >= f > g && h || i = j <END OF FILE>"
+    );
+}
+
+#[test]
+fn range_operators() {
+    use crate::frontend::lexer::tokenize;
+
+    let tokens: Vec<String> = tokenize("for i in 0..10 { } 1.5..=x".to_string())
+        .unwrap()
+        .into_iter()
+        .map(|t| t.node.to_string())
+        .collect();
+    assert_eq!(
+        tokens.join(" "),
+        "for i in 0 .. 10 { } 1.5 ..= x <END OF FILE>"
+    );
+}
+
+#[test]
+fn keywords_are_reserved() {
+    use crate::frontend::lexer::{find_reserved, KEYWORDS};
+
+    for (keyword, token) in KEYWORDS {
+        assert_eq!(token.to_string(), keyword);
+        assert_eq!(find_reserved(keyword), Some(token));
+    }
+    assert_eq!(find_reserved("lets"), None);
+}
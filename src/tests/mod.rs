@@ -1 +1,5 @@
+mod diagnostics;
 mod lexer;
+mod parser;
+mod repl;
+mod runtime;
@@ -0,0 +1,225 @@
+use crate::frontend::{
+    lexer::Token,
+    parser::{ParseError, Parser},
+};
+
+fn parse_errors(content: &str) -> Vec<(ParseError, usize, usize)> {
+    Parser::initialize(content.to_string())
+        .unwrap()
+        .produce_ast()
+        .unwrap_err()
+        .into_iter()
+        .map(|e| (e.node, e.span.line, e.span.column))
+        .collect()
+}
+
+#[test]
+fn statements_without_trailing_semicolon() {
+    let program = Parser::initialize("let a = 1; a = a + 2".to_string())
+        .unwrap()
+        .produce_ast()
+        .unwrap();
+    assert_eq!(program.body.len(), 2);
+    let program = Parser::initialize("{ let x } { let y = 1 }".to_string())
+        .unwrap()
+        .produce_ast()
+        .unwrap();
+    assert_eq!(program.body.len(), 2);
+}
+
+#[test]
+fn recovers_and_reports_every_error() {
+    let errors = parse_errors("let = 1;\nlet b = (1 + 2;\nconst c;\n1 + 2 = 3;\nlet d = 4;");
+    assert_eq!(errors.len(), 4);
+    assert!(matches!(
+        errors[0],
+        (ParseError::UnexpectedToken { .. }, 1, 5)
+    ));
+    assert!(matches!(
+        errors[1],
+        (ParseError::UnexpectedToken { .. }, 2, 15)
+    ));
+    assert!(matches!(
+        errors[2],
+        (ParseError::MissingConstInitializer(_), 3, 1)
+    ));
+    assert!(matches!(
+        errors[3],
+        (ParseError::InvalidAssignmentTarget, 4, 1)
+    ));
+}
+
+#[test]
+fn unexpected_eof() {
+    let errors = parse_errors("let a =");
+    assert!(matches!(
+        errors[..],
+        [(ParseError::UnexpectedEof { .. }, 1, 8)]
+    ));
+}
+
+#[test]
+fn literal_keywords_are_not_assignable() {
+    let errors = parse_errors("true = 5;\nlet false = 1;\nnull = true;");
+    assert!(matches!(
+        errors[0],
+        (ParseError::InvalidAssignmentTarget, 1, 1)
+    ));
+    assert!(matches!(
+        &errors[1],
+        (
+            ParseError::UnexpectedToken {
+                found: Token::False,
+                ..
+            },
+            2,
+            5
+        )
+    ));
+    assert!(matches!(
+        errors[2],
+        (ParseError::InvalidAssignmentTarget, 3, 1)
+    ));
+}
+
+#[test]
+fn errors_inside_blocks_are_recovered() {
+    let errors = parse_errors("{ let = 1; { 2 + ; } let a = 1 }\n}\n{ let b = 2;");
+    assert_eq!(errors.len(), 4);
+    assert!(matches!(
+        errors[0],
+        (ParseError::UnexpectedToken { .. }, 1, 7)
+    ));
+    assert!(matches!(
+        errors[1],
+        (ParseError::UnexpectedToken { .. }, 1, 18)
+    ));
+    assert!(matches!(
+        &errors[2],
+        (
+            ParseError::UnexpectedToken {
+                found: Token::CloseBrace,
+                ..
+            },
+            2,
+            1
+        )
+    ));
+    assert!(matches!(
+        errors[3],
+        (ParseError::UnexpectedEof { .. }, 3, 13)
+    ));
+    let errors = parse_errors("let b = 1 &&\n\n// trailing comment\n");
+    assert!(matches!(
+        &errors[..],
+        [(ParseError::UnexpectedEof { .. }, 1, 13)]
+    ));
+}
+
+#[test]
+fn loop_control_outside_loops() {
+    let errors = parse_errors("break;\nwhile true { if true { continue; } }\n{ continue }");
+    assert!(matches!(
+        &errors[..],
+        [
+            (ParseError::OutsideLoop(Token::Break), 1, 1),
+            (ParseError::OutsideLoop(Token::Continue), 3, 3)
+        ]
+    ));
+}
+
+#[test]
+fn return_outside_functions() {
+    let errors = parse_errors(
+        "return 1;\nfn f(a, b,) { return; }\nwhile true { fn g() { break; } }\nf(1 2);",
+    );
+    assert!(matches!(
+        &errors[..],
+        [
+            (ParseError::OutsideFunction, 1, 1),
+            (ParseError::OutsideLoop(Token::Break), 3, 23),
+            (ParseError::UnexpectedToken { .. }, 4, 5)
+        ]
+    ));
+}
+
+#[test]
+fn incomplete_input_is_told_apart() {
+    let is_incomplete = |content: &str| match Parser::initialize(content.to_string()) {
+        Ok(mut parser) => parser
+            .produce_ast()
+            .is_err_and(|errors| errors.iter().all(|e| e.node.is_incomplete_input())),
+        Err(errors) => errors.iter().all(|e| e.node.is_incomplete_input()),
+    };
+    for content in [
+        "let x =",
+        "f(1,",
+        "fn f(a) {\n  a",
+        "\"open\nstring",
+        "/* comment",
+    ] {
+        assert!(is_incomplete(content), "{content:?}");
+    }
+    for content in ["let = 1;", "let a = (1;", "1 $ 2", "}"] {
+        assert!(!is_incomplete(content), "{content:?}");
+    }
+}
+
+#[test]
+fn smallest_int_literal() {
+    use crate::frontend::{ast::Expression, ast::Statement, lexer::LexError};
+
+    let program = Parser::initialize("-2147483648".to_string())
+        .unwrap()
+        .produce_ast()
+        .unwrap();
+    assert!(matches!(
+        &program.body[0].node,
+        Statement::Expression(expression)
+            if matches!(expression.node, Expression::NumericLiteral(i32::MIN))
+    ));
+    let errors = parse_errors("2147483648;\nlet a = 1 - 2147483648;\n-(2147483648);");
+    assert_eq!(
+        errors,
+        vec![
+            (ParseError::InvalidNumber("2147483648".to_string()), 1, 1),
+            (ParseError::InvalidNumber("2147483648".to_string()), 2, 13),
+            (ParseError::InvalidNumber("2147483648".to_string()), 3, 3),
+        ]
+    );
+    let errors = Parser::initialize("-2147483649".to_string()).unwrap_err();
+    assert_eq!(
+        errors[0].node,
+        LexError::InvalidNumber("2147483649".to_string())
+    );
+}
+
+#[test]
+fn deep_nesting() {
+    let cases = |depth: usize| {
+        [
+            format!("{}1{}", "(".repeat(depth), ")".repeat(depth)),
+            format!("{}1{}", "[".repeat(depth), "]".repeat(depth)),
+            format!("{}{}", "{".repeat(depth), "}".repeat(depth)),
+            format!("{}1", "-".repeat(depth)),
+            format!("1{}", " + 1".repeat(depth)),
+        ]
+    };
+    // Test threads have a small stack, unlike the thread `main` runs the interpreter on.
+    std::thread::Builder::new()
+        .stack_size(crate::STACK_SIZE)
+        .spawn(move || {
+            for content in cases(250) {
+                assert!(Parser::initialize(content).unwrap().produce_ast().is_ok());
+            }
+            for content in cases(20000) {
+                let errors = parse_errors(&format!("let a = 1;\n{content};\nlet b = ;"));
+                assert_eq!(errors.len(), 2);
+                assert_eq!((&errors[0].0, errors[0].1), (&ParseError::NestedTooDeep, 2));
+                assert_eq!(errors[1].1, 3);
+            }
+        })
+        .unwrap()
+        .join()
+        .unwrap();
+}
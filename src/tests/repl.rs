@@ -0,0 +1,179 @@
+use crate::{
+    frontend::{ast::Program, parser::Parser},
+    repl::{evaluate_expression, evaluate_input},
+    runtime::{environment::Environment, values::RuntimeValue},
+};
+
+fn parse(input: &str) -> Program {
+    Parser::initialize(input.to_string())
+        .unwrap()
+        .produce_ast()
+        .unwrap()
+}
+
+fn evaluate(environment: &mut Environment, input: &str) -> Option<String> {
+    evaluate_input(environment, parse(input))
+        .unwrap()
+        .map(|value| value.to_string())
+}
+
+#[test]
+fn trailing_expressions_are_shown() {
+    let mut environment = Environment::create(None);
+    assert_eq!(evaluate(&mut environment, "1 + 2"), Some("3".to_string()));
+    assert_eq!(evaluate(&mut environment, "let a = 1;"), None);
+    assert_eq!(
+        evaluate(&mut environment, "a = a + 1; a * 2.0;"),
+        Some("4.0".to_string())
+    );
+    assert_eq!(evaluate(&mut environment, "null"), Some("null".to_string()));
+    assert_eq!(evaluate(&mut environment, "{ a; }"), None);
+    assert_eq!(
+        evaluate(
+            &mut environment,
+            r#"let m = { name: "a\"b", "two words": [1, 0..=2] }; m"#
+        ),
+        Some(r#"{name: "a\"b", "two words": [1, 0..=2]}"#.to_string())
+    );
+    assert_eq!(
+        evaluate(&mut environment, "let l = [1]; l[0] = l; l"),
+        Some("[[...]]".to_string())
+    );
+    assert_eq!(
+        evaluate(&mut environment, "fn add(x, y) { x + y } [add, z => z]"),
+        Some("[fn add(x, y), fn <anonymous>(z)]".to_string())
+    );
+    assert!(matches!(
+        environment.evaluate_identifier("a".to_string()),
+        Ok(RuntimeValue::NumberValue(2))
+    ));
+}
+
+#[test]
+fn only_single_expressions_are_typed() {
+    let mut environment = Environment::create(None);
+    evaluate(
+        &mut environment,
+        "let x = 1;\nfn bump() { x = x + 1; return x; }",
+    );
+    let mut type_of = |input: &str| {
+        evaluate_expression(&mut environment, parse(input)).map(|value| value.unwrap().type_name())
+    };
+    assert_eq!(type_of("x + 0.5"), Some("float"));
+    assert_eq!(type_of("let q = 5;"), None);
+    assert_eq!(type_of("x = 5;"), None);
+    assert_eq!(type_of("x; x"), None);
+    assert_eq!(type_of("bump()"), Some("int"));
+    assert!(environment.resolve("q").is_none());
+    assert!(matches!(
+        environment.evaluate_identifier("x".to_string()),
+        Ok(RuntimeValue::NumberValue(2))
+    ));
+}
+
+#[test]
+fn keys_are_decoded() {
+    use crate::repl::line_editor::{read_key, Key};
+
+    let mut input: &[u8] = b"a\x01\x17\x12\r\x7f\t\x1b[A\x1b[D\x1bOH\x1b[4~\x1b[3~\x1b[5~\xc3\xa9";
+    let mut keys = Vec::new();
+    while let Some(key) = read_key(&mut input).unwrap() {
+        keys.push(key);
+    }
+    assert_eq!(
+        keys,
+        vec![
+            Key::Char('a'),
+            Key::Ctrl('a'),
+            Key::Ctrl('w'),
+            Key::Ctrl('r'),
+            Key::Enter,
+            Key::Backspace,
+            Key::Tab,
+            Key::Up,
+            Key::Left,
+            Key::Home,
+            Key::End,
+            Key::Delete,
+            Key::Unknown,
+            Key::Char('é'),
+        ]
+    );
+}
+
+#[test]
+fn lines_are_edited() {
+    use crate::repl::line_editor::Buffer;
+
+    let mut buffer = Buffer::new("let value = 1");
+    buffer.delete_word();
+    assert_eq!(buffer.text(), "let value = ");
+    buffer.delete_word();
+    assert_eq!(buffer.text(), "let value ");
+    buffer.move_home();
+    buffer.insert("é ");
+    buffer.move_right();
+    buffer.delete();
+    assert_eq!(
+        (buffer.text(), buffer.cursor()),
+        ("é lt value ".to_string(), 3)
+    );
+    buffer.move_end();
+    buffer.backspace();
+    buffer.move_left();
+    assert_eq!(buffer.word_before_cursor(), "valu");
+}
+
+#[test]
+fn keywords_and_names_in_scope_are_completed() {
+    use crate::repl::{
+        completions,
+        line_editor::{longest_common_prefix, search_history},
+    };
+
+    let mut environment = Environment::create(None);
+    environment
+        .declare_variable("count", RuntimeValue::NumberValue(1))
+        .unwrap()
+        .declare_constant("constant_value", RuntimeValue::NullValue)
+        .unwrap();
+    let mut candidates = completions("co", &Environment::create(Some(environment)));
+    candidates.sort();
+    assert_eq!(
+        candidates,
+        vec!["const", "constant_value", "continue", "count"]
+    );
+    assert_eq!(longest_common_prefix(&candidates), "co");
+    assert_eq!(longest_common_prefix(&candidates[..2]), "const");
+
+    let history = ["let a = 1;", "a + 2", "let b = a;"].map(String::from);
+    assert_eq!(search_history(&history, "let", 3), Some(2));
+    assert_eq!(search_history(&history, "let", 2), Some(0));
+    assert_eq!(search_history(&history, "c", 3), None);
+}
+
+#[test]
+fn errors_are_located_in_their_source() {
+    use crate::repl::sources::Sources;
+
+    let mut sources = Sources::default();
+    let mut environment = Environment::create(None);
+    let mut evaluate_source = |name: &str, text: &str| {
+        let offset = sources.add(name, text);
+        let program = Parser::initialize_at(text.to_string(), offset)
+            .unwrap()
+            .produce_ast()
+            .unwrap();
+        evaluate_input(&mut environment, program)
+    };
+    evaluate_source("lib.awa", "fn f(x) {\n  x + missing\n}").unwrap();
+    evaluate_source("<shell>", "1").unwrap();
+    let error = evaluate_source("<shell>", "2;\nf(1)").unwrap_err();
+    let (name, text, offset) = sources.locate(error.span.start).unwrap();
+    assert_eq!(name, "lib.awa");
+    assert_eq!(
+        &text[error.span.start - offset..error.span.end - offset],
+        "missing"
+    );
+    assert_eq!((error.span.line, error.span.column), (2, 7));
+}
@@ -0,0 +1,645 @@
+use crate::{
+    frontend::{parser::Parser, span::Spanned},
+    runtime::{
+        environment::{EnvError, Environment},
+        values::RuntimeValue,
+    },
+};
+
+/// Runs `content` in a fresh environment.
+fn run(content: &str) -> Result<Environment, Spanned<EnvError>> {
+    let program = Parser::initialize(content.to_string())
+        .unwrap()
+        .produce_ast()
+        .unwrap();
+    let mut environment = Environment::create(None);
+    environment.evaluate_program(program)?;
+    Ok(environment)
+}
+
+fn value_of(environment: &mut Environment, identifier: &str) -> RuntimeValue {
+    environment
+        .evaluate_identifier(identifier.to_string())
+        .unwrap()
+}
+
+#[test]
+fn runtime_error_span() {
+    let source = "let a = 1;\nlet b = a + missing;";
+    let error = run(source).err().unwrap();
+    assert_eq!(&source[error.span.start..error.span.end], "missing");
+    assert_eq!((error.span.line, error.span.column), (2, 13));
+}
+
+#[test]
+fn string_concatenation() {
+    let mut environment = run(r#"let a = "foo"; let b = a + "\tbar\u{21}";"#).unwrap();
+    assert!(
+        matches!(value_of(&mut environment, "b"), RuntimeValue::String(s) if &*s == "foo\tbar!")
+    );
+}
+
+#[test]
+fn string_arithmetic_is_a_type_error() {
+    let error = run(r#"let a = "foo" * 2;"#).err().unwrap();
+    assert_eq!(
+        error.node,
+        EnvError::InvalidOperands {
+            operator: "*".to_string(),
+            left: "string",
+            right: "int"
+        }
+    );
+}
+
+#[test]
+fn numeric_promotion() {
+    let mut environment =
+        run("let a = 7 / 2; let b = 7 / 2.0; let c = 1e-3 * 1000; let d = 2.5 % 2;").unwrap();
+    assert!(matches!(
+        value_of(&mut environment, "a"),
+        RuntimeValue::NumberValue(3)
+    ));
+    assert!(matches!(value_of(&mut environment, "b"), RuntimeValue::Float(f) if f == 3.5));
+    assert!(matches!(value_of(&mut environment, "c"), RuntimeValue::Float(f) if f == 1.0));
+    assert!(matches!(value_of(&mut environment, "d"), RuntimeValue::Float(f) if f == 0.5));
+}
+
+#[test]
+fn comparison_and_equality() {
+    let mut environment = run(r#"
+        let a = 1 + 2 == 3;
+        let b = 2 < 1.5 || "abc" < "abd";
+        let c = 1 == 1.0 && "1" != 1;
+        let d = 3 >= 4 == 2 > 3;
+    "#)
+    .unwrap();
+    for identifier in ["a", "b", "c", "d"] {
+        assert!(matches!(
+            value_of(&mut environment, identifier),
+            RuntimeValue::Bool(true)
+        ));
+    }
+}
+
+#[test]
+fn logical_operators_short_circuit() {
+    // `missing` is never evaluated, and `1` is never checked for being a bool.
+    let mut environment = run("let a = 1 > 2 && missing; let b = 1 < 2 || 1;").unwrap();
+    assert!(matches!(
+        value_of(&mut environment, "a"),
+        RuntimeValue::Bool(false)
+    ));
+    assert!(matches!(
+        value_of(&mut environment, "b"),
+        RuntimeValue::Bool(true)
+    ));
+
+    let error = run("let a = 1 < 2 && 1;").err().unwrap();
+    assert_eq!(
+        error.node,
+        EnvError::InvalidOperand {
+            operator: "&&".to_string(),
+            operand: "int"
+        }
+    );
+    assert_eq!(error.span.column, 18);
+}
+
+#[test]
+fn unary_operators() {
+    let mut environment =
+        run("let a = 2 * -3; let b = -a - -1.5; let c = !(a < 0) || !!true; let d = +4 - -(-1);")
+            .unwrap();
+    assert!(matches!(
+        value_of(&mut environment, "a"),
+        RuntimeValue::NumberValue(-6)
+    ));
+    assert!(matches!(value_of(&mut environment, "b"), RuntimeValue::Float(f) if f == 7.5));
+    assert!(matches!(
+        value_of(&mut environment, "c"),
+        RuntimeValue::Bool(true)
+    ));
+    assert!(matches!(
+        value_of(&mut environment, "d"),
+        RuntimeValue::NumberValue(3)
+    ));
+
+    let error = run(r#"let a = 1 + -"x";"#).err().unwrap();
+    assert_eq!(
+        error.node,
+        EnvError::InvalidOperand {
+            operator: "-".to_string(),
+            operand: "string"
+        }
+    );
+    assert_eq!(
+        (error.span.column, error.span.end - error.span.start),
+        (13, 4)
+    );
+}
+
+#[test]
+fn literal_keywords() {
+    let mut environment =
+        run("let a = true && !false; let b = null == null; let c = null;").unwrap();
+    assert!(matches!(
+        value_of(&mut environment, "a"),
+        RuntimeValue::Bool(true)
+    ));
+    assert!(matches!(
+        value_of(&mut environment, "b"),
+        RuntimeValue::Bool(true)
+    ));
+    assert!(matches!(
+        value_of(&mut environment, "c"),
+        RuntimeValue::NullValue
+    ));
+}
+
+#[test]
+fn blocks_have_their_own_scope() {
+    let mut environment = run(r#"
+        let a = 1;
+        let b = 1;
+        {
+            let a = "shadowed";
+            let c = a + "!";
+            b = b + 1;
+            {
+                b = b * 10;
+                a = c;
+            }
+        }
+    "#)
+    .unwrap();
+    assert!(matches!(
+        value_of(&mut environment, "a"),
+        RuntimeValue::NumberValue(1)
+    ));
+    assert!(matches!(
+        value_of(&mut environment, "b"),
+        RuntimeValue::NumberValue(20)
+    ));
+    assert_eq!(
+        environment
+            .evaluate_identifier("c".to_string())
+            .unwrap_err(),
+        EnvError::VarNotFound("c".to_string())
+    );
+}
+
+#[test]
+fn if_else_chains() {
+    let mut environment = run(r#"
+        let a = 5;
+        let size = "";
+        if (a < 3) {
+            size = "small";
+        } else if a < 10 {
+            size = "medium";
+        } else {
+            size = "large";
+        }
+        let empty = 0;
+        if "" { empty = 1; } else if null { empty = 2; } else if 0.0 { empty = 3; }
+    "#)
+    .unwrap();
+    assert!(
+        matches!(value_of(&mut environment, "size"), RuntimeValue::String(s) if &*s == "medium")
+    );
+    assert!(matches!(
+        value_of(&mut environment, "empty"),
+        RuntimeValue::NumberValue(0)
+    ));
+}
+
+#[test]
+fn strict_conditions_must_be_bools() {
+    let program = Parser::initialize("let a = 1; if a { a = 2; }".to_string())
+        .unwrap()
+        .produce_ast()
+        .unwrap();
+    let mut environment = Environment::create(None);
+    let error = environment
+        .set_strict_conditions(true)
+        .evaluate_program(program)
+        .map(|_| ())
+        .unwrap_err();
+    assert_eq!(error.node, EnvError::NonBoolCondition("int"));
+    assert_eq!(error.span.column, 15);
+}
+
+#[test]
+fn while_loops_with_break_and_continue() {
+    let mut environment = run(r#"
+        let i = 0;
+        let sum = 0;
+        while true {
+            i = i + 1;
+            if i % 2 == 0 { continue; }
+            if i > 9 { break; }
+            let j = 0;
+            while j < i { j = j + 1; sum = sum + 1; }
+        }
+    "#)
+    .unwrap();
+    assert!(matches!(
+        value_of(&mut environment, "i"),
+        RuntimeValue::NumberValue(11)
+    ));
+    assert!(matches!(
+        value_of(&mut environment, "sum"),
+        RuntimeValue::NumberValue(25)
+    ));
+}
+
+#[test]
+fn for_loops_over_ranges_and_strings() {
+    let mut environment = run(r#"
+        let sum = 0;
+        for i in 0..10 {
+            if i == 3 { continue; }
+            if i == 8 { break; }
+            sum = sum + i;
+        }
+        let inclusive = 0;
+        for i in 1..=4 { inclusive = inclusive + i; i = 0; }
+        let reversed = "";
+        for c in "abc" { reversed = c + reversed; }
+    "#)
+    .unwrap();
+    assert!(matches!(
+        value_of(&mut environment, "sum"),
+        RuntimeValue::NumberValue(25)
+    ));
+    assert!(matches!(
+        value_of(&mut environment, "inclusive"),
+        RuntimeValue::NumberValue(10)
+    ));
+    assert!(
+        matches!(value_of(&mut environment, "reversed"), RuntimeValue::String(s) if &*s == "cba")
+    );
+    assert_eq!(
+        environment
+            .evaluate_identifier("i".to_string())
+            .unwrap_err(),
+        EnvError::VarNotFound("i".to_string())
+    );
+
+    let error = run("for i in 3 { }").err().unwrap();
+    assert_eq!(error.node, EnvError::NotIterable("int"));
+    assert_eq!(error.span.column, 10);
+}
+
+#[test]
+fn functions_and_calls() {
+    let mut environment = run(r#"
+        fn fib(n) {
+            if n < 2 { return n; }
+            return fib(n - 1) + fib(n - 2);
+        }
+        fn first_even(limit) {
+            for i in 1..limit {
+                if i % 2 == 0 { return i; }
+            }
+        }
+        fn nothing() { let local = 1; }
+        let a = fib(10);
+        let b = first_even(10);
+        let c = first_even(2);
+        let d = nothing();
+    "#)
+    .unwrap();
+    assert!(matches!(
+        value_of(&mut environment, "a"),
+        RuntimeValue::NumberValue(55)
+    ));
+    assert!(matches!(
+        value_of(&mut environment, "b"),
+        RuntimeValue::NumberValue(2)
+    ));
+    assert!(matches!(
+        value_of(&mut environment, "c"),
+        RuntimeValue::NullValue
+    ));
+    assert!(matches!(
+        value_of(&mut environment, "d"),
+        RuntimeValue::NullValue
+    ));
+    assert!(environment
+        .evaluate_identifier("local".to_string())
+        .is_err());
+
+    let error = run("fn add(a, b) { return a + b; }\nadd(1);")
+        .err()
+        .unwrap();
+    assert_eq!(
+        error.node,
+        EnvError::ArityMismatch {
+            function: "add".to_string(),
+            expected: 2,
+            found: 1
+        }
+    );
+    assert_eq!((error.span.line, error.span.column), (2, 1));
+
+    let error = run("let count = 3; count();").err().unwrap();
+    assert_eq!(error.node, EnvError::NotCallable("int"));
+}
+
+#[test]
+fn closures_capture_their_scope() {
+    let mut environment = run(r#"
+        fn make_counter() {
+            let count = 0;
+            fn() { count = count + 1; count }
+        }
+        let counter = make_counter();
+        counter();
+        counter();
+        let counted = counter();
+        let other = make_counter()();
+
+        let total = 0;
+        fn accumulate(n) { total = total + n; }
+        for i in 1..=4 { accumulate(i); }
+
+        let n = 1;
+        let add_n = x => x + n;
+        n = 10;
+        let shifted = add_n(5);
+    "#)
+    .unwrap();
+    assert!(matches!(
+        value_of(&mut environment, "counted"),
+        RuntimeValue::NumberValue(3)
+    ));
+    assert!(matches!(
+        value_of(&mut environment, "other"),
+        RuntimeValue::NumberValue(1)
+    ));
+    assert!(matches!(
+        value_of(&mut environment, "total"),
+        RuntimeValue::NumberValue(10)
+    ));
+    assert!(matches!(
+        value_of(&mut environment, "shifted"),
+        RuntimeValue::NumberValue(15)
+    ));
+    assert!(environment
+        .evaluate_identifier("count".to_string())
+        .is_err());
+}
+
+#[test]
+fn arrays_are_shared_and_indexable() {
+    let mut environment = run(r#"
+        let a = [1, 2, [3, 4],];
+        let b = a;
+        b[0] = 10;
+        a[2][1] = 40;
+        fn set_first(array, value) { array[0] = value; }
+        let c = [0];
+        set_first(c, "x");
+        let sum = 0;
+        for n in [1, 2, 3] { sum = sum + n; }
+        let first = a[0];
+        let nested = b[2][1];
+        let same = [1, [2]] == [1.0, [2]];
+    "#)
+    .unwrap();
+    assert!(matches!(
+        value_of(&mut environment, "first"),
+        RuntimeValue::NumberValue(10)
+    ));
+    assert!(matches!(
+        value_of(&mut environment, "nested"),
+        RuntimeValue::NumberValue(40)
+    ));
+    assert!(matches!(
+        value_of(&mut environment, "sum"),
+        RuntimeValue::NumberValue(6)
+    ));
+    assert!(matches!(
+        value_of(&mut environment, "same"),
+        RuntimeValue::Bool(true)
+    ));
+    let RuntimeValue::Array(c) = value_of(&mut environment, "c") else {
+        panic!("c should be an array");
+    };
+    assert!(matches!(&c.borrow()[0], RuntimeValue::String(s) if &**s == "x"));
+
+    environment = run(r#"
+        let a = [1]; a[0] = a;
+        let b = [1]; b[0] = b;
+        let cycles = a == b;
+        let different = [a, 1] == [b, 2];
+    "#)
+    .unwrap();
+    assert!(matches!(
+        value_of(&mut environment, "cycles"),
+        RuntimeValue::Bool(true)
+    ));
+    assert!(matches!(
+        value_of(&mut environment, "different"),
+        RuntimeValue::Bool(false)
+    ));
+
+    let error = run("let a = [1, 2];\na[-1];").err().unwrap();
+    assert_eq!(error.node, EnvError::NegativeIndex(-1));
+    assert_eq!((error.span.line, error.span.column), (2, 3));
+    let error = run("let a = [1, 2]; a[2] = 3;").err().unwrap();
+    assert_eq!(
+        error.node,
+        EnvError::IndexOutOfBounds {
+            index: 2,
+            length: 2
+        }
+    );
+    let error = run("let a = 1; a[0];").err().unwrap();
+    assert!(matches!(
+        error.node,
+        EnvError::InvalidOperands { left: "int", .. }
+    ));
+}
+
+#[test]
+fn maps_keep_insertion_order() {
+    let mut environment = run(r#"
+        let user = { name: "x", "count": 3, };
+        user.count = user["count"] + 1;
+        user["zeta"] = 1;
+        user.alpha = 2;
+        let keys = "";
+        for key in user { keys = keys + key + " "; }
+        let alias = user;
+        alias.name = "y";
+        let name = user.name;
+        let nested = { inner: { value: [1, 2] } };
+        nested.inner.value[1] = 5;
+        let deep = nested["inner"].value[1];
+        { user: 1 };
+        { let block = 1; }
+        let equal = { a: 1, b: 2 } == { b: 2, a: 1.0 };
+    "#)
+    .unwrap();
+    assert!(
+        matches!(value_of(&mut environment, "keys"), RuntimeValue::String(s) if &*s == "name count zeta alpha ")
+    );
+    assert!(matches!(value_of(&mut environment, "name"), RuntimeValue::String(s) if &*s == "y"));
+    assert!(matches!(
+        value_of(&mut environment, "deep"),
+        RuntimeValue::NumberValue(5)
+    ));
+    assert!(matches!(
+        value_of(&mut environment, "equal"),
+        RuntimeValue::Bool(true)
+    ));
+
+    environment = run(r#"
+        let a = { key: 1 }; a.self = a;
+        let b = { key: 1 }; b.self = b;
+        let cycles = a == b;
+        b.key = 2;
+        let different = a == b;
+    "#)
+    .unwrap();
+    assert!(matches!(
+        value_of(&mut environment, "cycles"),
+        RuntimeValue::Bool(true)
+    ));
+    assert!(matches!(
+        value_of(&mut environment, "different"),
+        RuntimeValue::Bool(false)
+    ));
+
+    let error = run("let user = { name: 1 };\nuser.age;").err().unwrap();
+    assert_eq!(error.node, EnvError::MissingKey("age".to_string()));
+    assert_eq!((error.span.line, error.span.column), (2, 1));
+    let error = run("let a = 1; a.field = 2;").err().unwrap();
+    assert!(matches!(
+        error.node,
+        EnvError::InvalidOperand { operand: "int", .. }
+    ));
+}
+
+#[test]
+fn constants_are_readable_but_not_assignable() {
+    let mut environment = run(r#"
+        const limit = 10;
+        const items = [1];
+        items[0] = 2;
+        let outer = 0;
+        { { outer = limit + items[0]; } }
+        fn bump() { outer = outer + 1; }
+        bump();
+        { let limit = 1; limit = 2; }
+    "#)
+    .unwrap();
+    assert!(matches!(
+        value_of(&mut environment, "limit"),
+        RuntimeValue::NumberValue(10)
+    ));
+    assert!(matches!(
+        value_of(&mut environment, "outer"),
+        RuntimeValue::NumberValue(13)
+    ));
+
+    let error = run("const limit = 10;\n{ limit = 20; }").err().unwrap();
+    assert_eq!(error.node, EnvError::ConstAssignment("limit".to_string()));
+    assert_eq!((error.span.line, error.span.column), (2, 3));
+    let error = run("missing = 1;").err().unwrap();
+    assert_eq!(error.node, EnvError::VarNotFound("missing".to_string()));
+}
+
+#[test]
+fn int_overflow_and_division_by_zero() {
+    use crate::runtime::environment::OverflowMode;
+
+    let error = run("let a = 1;\nlet b = a / 0;").err().unwrap();
+    assert_eq!(error.node, EnvError::DivisionByZero("/".to_string()));
+    assert_eq!((error.span.line, error.span.column), (2, 9));
+    let error = run("1 % 0;").err().unwrap();
+    assert_eq!(error.node, EnvError::DivisionByZero("%".to_string()));
+    let error = run("2147483647 + 1;").err().unwrap();
+    assert_eq!(
+        error.node,
+        EnvError::IntegerOverflow {
+            operator: "+".to_string(),
+            operands: vec![2147483647, 1]
+        }
+    );
+    let error = run("let min = -2147483648; -min;").err().unwrap();
+    assert_eq!(
+        error.node,
+        EnvError::IntegerOverflow {
+            operator: "-".to_string(),
+            operands: vec![i32::MIN]
+        }
+    );
+    assert!(run("let f = 1.0 / 0;").is_ok());
+
+    let source = "let a = 2147483647 + 1; let b = -2147483647 * 2; let c = -(-2147483648);";
+    for (mode, expected) in [
+        (OverflowMode::Wrapping, [i32::MIN, 2, i32::MIN]),
+        (OverflowMode::Saturating, [i32::MAX, i32::MIN, i32::MAX]),
+    ] {
+        let program = Parser::initialize(source.to_string())
+            .unwrap()
+            .produce_ast()
+            .unwrap();
+        let mut environment = Environment::create(None);
+        environment
+            .set_overflow_mode(mode)
+            .evaluate_program(program)
+            .unwrap();
+        for (name, expected) in ["a", "b", "c"].into_iter().zip(expected) {
+            assert!(matches!(
+                value_of(&mut environment, name),
+                RuntimeValue::NumberValue(n) if n == expected
+            ));
+        }
+    }
+}
+
+#[test]
+fn bindings_are_listed_with_shadowing() {
+    let environment = run("const limit = 10; let b = 1; let a = \"x\";").unwrap();
+    let mut scope = Environment::create(Some(environment.clone()));
+    scope
+        .declare_variable("limit", RuntimeValue::Bool(true))
+        .unwrap();
+    let listed: Vec<String> = scope
+        .bindings()
+        .into_iter()
+        .map(|(name, value, constant)| format!("{constant} {name} = {value}"))
+        .collect();
+    assert_eq!(
+        listed,
+        ["false a = \"x\"", "false b = 1", "false limit = true"]
+    );
+    assert!(matches!(
+        environment.bindings()[2],
+        (ref name, RuntimeValue::NumberValue(10), true) if name == "limit"
+    ));
+}
+
+#[test]
+fn runaway_recursion_is_an_error() {
+    // Test threads have a small stack, unlike the thread `main` runs the interpreter on.
+    let error = std::thread::Builder::new()
+        .stack_size(crate::STACK_SIZE)
+        .spawn(|| {
+            run("fn deep(n) { if n == 0 { return 0; } return 1 + deep(n - 1); }\ndeep(999);")
+                .unwrap();
+            run("fn forever(n) { return forever(n + 1); }\nforever(0);")
+                .err()
+                .unwrap()
+        })
+        .unwrap()
+        .join()
+        .unwrap();
+    assert_eq!(error.node, EnvError::RecursionLimit("forever".to_string()));
+    assert_eq!((error.span.line, error.span.column), (1, 24));
+}